@@ -1,12 +1,19 @@
 use failure::Fallible;
+use hacky_detection::DetectorParams;
 use realsense_rust::kind::Format;
-use serde::{de::Error, Deserialize, Deserializer};
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
 use std::{
     fs::File,
     io::{prelude::*, BufReader},
     path::{Path, PathBuf},
 };
 
+/// The prefix of environment variables that override configuration fields.
+/// The rest of the name is the field path separated by double underscores,
+/// e.g. `HACKY_ARM__CONTROLLER__TRANSLATION=[373,167]`.
+pub const ENV_PREFIX: &str = "HACKY_ARM__";
+
 /// The global configuration type.
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
@@ -16,6 +23,10 @@ pub struct Config {
     pub object_detector: ObjectDetectorConfig,
     pub visualizer: VisualizerConfig,
     pub controller: ControllerConfig,
+    #[serde(default)]
+    pub recorder: RecorderConfig,
+    #[serde(default)]
+    pub tracker: TrackerConfig,
 }
 
 /// The Dobot configuration.
@@ -35,17 +46,161 @@ pub struct ControllerConfig {
     pub translation: [f64; 2],
 
     /// depth pair of (image, robot)
-    pub depth_image: [f32; 9],
-    pub depth_robot: [f32; 9],
+    pub depth_image: Vec<f32>,
+    pub depth_robot: Vec<f32>,
+
+    /// the labels of objects to grab, any object if empty
+    #[serde(default)]
+    pub target_labels: Vec<String>,
+
+    /// the model fitted from depth pairs to compute robot z
+    #[serde(default)]
+    pub depth_model: DepthModelConfig,
+    /// the [min, max] range of robot z, the range of depth_robot by default
+    #[serde(default)]
+    pub z_limits: Option<[f32; 2]>,
+    /// how far in meters the depth can go beyond the calibrated range
+    #[serde(default = "default_max_extrapolation")]
+    pub max_extrapolation: f32,
+
+    /// the condition for the target to be grabbed automatically
+    #[serde(default)]
+    pub stability: StabilityConfig,
+
+    /// the rotation limits and opening of the gripper
+    #[serde(default)]
+    pub gripper: GripperConfig,
+}
+
+/// The kind of depth-to-robot-z model.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DepthModelConfig {
+    PiecewiseLinear,
+    /// least squares polynomial fitting
+    Polynomial {
+        degree: usize,
+    },
+}
+
+impl Default for DepthModelConfig {
+    fn default() -> Self {
+        DepthModelConfig::PiecewiseLinear
+    }
 }
 
 /// The RealSense configuration.
 #[derive(Debug, Clone, Deserialize)]
 pub struct RealSenseConfig {
+    #[serde(default)]
+    pub source: FrameSourceConfig,
+    /// the software filters applied on depth images in order
+    #[serde(default)]
+    pub depth_filters: Vec<DepthFilterConfig>,
     pub depth_camera: DepthCameraConfig,
     pub video_camera: VideoCameraConfig,
 }
 
+/// The selection of frame source. It defaults to the RealSense device.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FrameSourceConfig {
+    Device,
+    Playback(PlaybackConfig),
+    Synthetic(SyntheticConfig),
+}
+
+impl Default for FrameSourceConfig {
+    fn default() -> Self {
+        FrameSourceConfig::Device
+    }
+}
+
+/// A software depth filter. Depth thresholds are measured in meters.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DepthFilterConfig {
+    /// Downsamples the depth by the median of each factor x factor block.
+    Decimation { factor: usize },
+    /// Edge-preserving smoothing within a frame.
+    Spatial {
+        #[serde(default = "default_spatial_alpha")]
+        alpha: f32,
+        #[serde(default = "default_filter_delta")]
+        delta: f32,
+        #[serde(default = "default_spatial_iterations")]
+        iterations: usize,
+    },
+    /// Exponential moving average across frames.
+    Temporal {
+        #[serde(default = "default_temporal_alpha")]
+        alpha: f32,
+        #[serde(default = "default_filter_delta")]
+        delta: f32,
+    },
+    /// Fills zero depth pixels from their neighbors.
+    HoleFilling { mode: HoleFillingMode },
+}
+
+/// The way to pick the depth for holes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoleFillingMode {
+    FillFromLeft,
+    FarthestFromAround,
+    NearestFromAround,
+}
+
+/// The recorded session playback configuration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaybackConfig {
+    /// the recorded session directory
+    pub dir: PathBuf,
+    /// the playback speed relative to the recorded rate
+    #[serde(default = "default_playback_speed")]
+    pub speed: f64,
+    /// restart from the first frame when reaching the end
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+/// The synthetic scene configuration.
+///
+/// The camera looks straight down to a flat table. Positions are measured
+/// in meters in camera coordinates, where x points right and y points down
+/// in the image.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyntheticConfig {
+    pub width: usize,
+    pub height: usize,
+    pub fps: f64,
+    /// horizontal field of view in degrees
+    #[serde(default = "default_synthetic_fov")]
+    pub fov: f32,
+    /// distance from the camera to the table in meters
+    pub table_distance: f32,
+    /// HSV color of the table in OpenCV ranges
+    pub table_color: [u8; 3],
+    /// height of a brick layer in meters
+    pub layer_height: f32,
+    pub bricks: Vec<BrickConfig>,
+}
+
+/// A stack of bricks in the synthetic scene.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BrickConfig {
+    /// HSV color in OpenCV ranges
+    pub color: [u8; 3],
+    /// length and width in meters
+    pub size: [f32; 2],
+    /// center on the table plane in meters
+    pub position: [f32; 2],
+    /// counter-clockwise rotation of the long side from image x axis in degrees
+    pub angle: f32,
+    /// number of stacked bricks
+    pub n_layers: usize,
+}
+
 /// The depth camera configuration on RealSense.
 #[derive(Debug, Clone, Deserialize)]
 pub struct DepthCameraConfig {
@@ -67,26 +222,87 @@ pub struct VideoCameraConfig {
 }
 
 /// The RealSense configuration.
-#[derive(Debug, Clone, Deserialize)]
-pub struct ObjectDetectorOrigConfig {
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ObjectDetectorConfig {
+    /// the parameter file where the configuration is loaded from
+    #[serde(skip)]
     pub params_file: Option<PathBuf>,
+    /// the inline fields in the configuration, which override the ones in
+    /// the parameter file and are applied again when the file is reloaded
+    #[serde(skip)]
+    pub overrides: Map<String, Value>,
+    /// the detector parameters, shared with the detection tools
+    #[serde(flatten)]
+    pub detector: DetectorParams,
+    /// the statistic over depth pixels within an object, median by default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth_statistic: Option<DepthStatistic>,
+    /// the minimum number of valid depth pixels for an object to be grabbed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_depth_pixels: Option<usize>,
+    /// segments objects standing above the table plane
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub depth_segmentation: Option<DepthSegmentationConfig>,
 }
 
-/// The RealSense configuration.
-#[derive(Debug, Clone, Deserialize)]
-pub struct ObjectDetectorConfig {
-    pub inversion: Option<bool>,
-    pub blur_kernel: Option<i32>,
-    pub n_dilations: Option<i32>,
-    pub dilation_kernel: Option<i32>,
-    pub n_erosions: Option<i32>,
-    pub erosion_kernel: Option<i32>,
-    pub n_objects: Option<usize>,
-    pub min_arc_length: Option<f64>,
-    pub max_arc_length: Option<f64>,
-    pub roi: Option<[f64; 2]>,
-    pub lower_bound: Option<[i32; 3]>,
-    pub upper_bound: Option<[i32; 3]>,
+/// The depth-based object segmentation configuration. Heights are measured
+/// in meters above the table plane fitted from the depth image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DepthSegmentationConfig {
+    #[serde(default)]
+    pub mode: SegmentationMode,
+    /// the label of objects found by depth
+    #[serde(default = "default_segmentation_label")]
+    pub label: String,
+    pub min_height: f32,
+    #[serde(default)]
+    pub max_height: Option<f32>,
+    /// the maximum distance from the plane for table points
+    #[serde(default = "default_plane_distance")]
+    pub plane_distance: f32,
+    /// the number of RANSAC iterations to fit the table plane
+    #[serde(default = "default_plane_iterations")]
+    pub n_iterations: usize,
+    /// fit the plane on every n-th pixel in both directions
+    #[serde(default = "default_sample_stride")]
+    pub sample_stride: usize,
+}
+
+/// The way to use depth segmentation along with color classes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentationMode {
+    /// use depth segmentation only
+    Replace,
+    /// detect objects from both color classes and depth
+    Combine,
+}
+
+impl Default for SegmentationMode {
+    fn default() -> Self {
+        SegmentationMode::Replace
+    }
+}
+
+/// The statistic to estimate object depth from the depth pixels within it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DepthStatistic {
+    Median,
+    /// the mean after dropping the ratio of smallest and largest values
+    TrimmedMean {
+        ratio: f32,
+    },
+    /// the percentile in [0, 100]
+    Percentile {
+        percentile: f32,
+    },
+}
+
+impl Default for DepthStatistic {
+    fn default() -> Self {
+        DepthStatistic::Median
+    }
 }
 
 /// The visualizer configuration.
@@ -96,6 +312,98 @@ pub struct VisualizerConfig {
     pub enable_video_viewer: bool,
     pub enable_depth_viewer: bool,
     pub enable_detection_viewer: bool,
+    /// the names of intermediate detection images to show, e.g. "brick/mask"
+    #[serde(default)]
+    pub debug_images: Vec<String>,
+}
+
+/// The condition that the target pose is stable enough to grab.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct StabilityConfig {
+    /// the number of consecutive frames the target must be observed
+    pub n_frames: usize,
+    /// the maximum distance in pixels from the averaged position
+    pub max_position_deviation: f32,
+    /// the maximum difference in degrees from the averaged angle
+    pub max_angle_deviation: f32,
+    /// the maximum difference in meters from the averaged depth
+    pub max_depth_deviation: f32,
+}
+
+impl Default for StabilityConfig {
+    fn default() -> Self {
+        Self {
+            n_frames: 5,
+            max_position_deviation: 3.0,
+            max_angle_deviation: 5.0,
+            max_depth_deviation: 0.005,
+        }
+    }
+}
+
+/// The gripper configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct GripperConfig {
+    /// the [min, max] range of joint 4 in degrees, relative to the base
+    pub joint4_limits: [f32; 2],
+    /// the widest object in meters the gripper can close on, unchecked if not set
+    pub max_opening: Option<f32>,
+}
+
+impl Default for GripperConfig {
+    fn default() -> Self {
+        Self {
+            joint4_limits: [-90.0, 90.0],
+            max_opening: None,
+        }
+    }
+}
+
+/// The multi-frame object tracker configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TrackerConfig {
+    /// the minimum bounding box IoU to match an object to a track
+    pub min_overlap: f32,
+    /// the maximum center distance in pixels to match an object to a track
+    pub max_distance: f32,
+    /// the number of frames a track survives without matched objects
+    pub max_misses: usize,
+    /// the weight of new observations in the smoothed pose, in (0, 1]
+    pub smoothing: f32,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            min_overlap: 0.3,
+            max_distance: 20.0,
+            max_misses: 3,
+            smoothing: 0.5,
+        }
+    }
+}
+
+/// The frame recorder configuration.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecorderConfig {
+    /// start recording on startup
+    #[serde(default)]
+    pub enabled: bool,
+    /// the directory where recorded sessions are saved
+    #[serde(default = "default_recorder_dir")]
+    pub dir: PathBuf,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_recorder_dir(),
+        }
+    }
 }
 
 impl Config {
@@ -104,14 +412,240 @@ impl Config {
     where
         P: AsRef<Path>,
     {
+        Self::from_value(load_value(path)?)
+    }
+
+    /// Parses the configuration from a merged JSON value.
+    pub fn from_value(value: Value) -> Fallible<Self> {
+        let config: Self = serde_json::from_value(value)?;
+        Ok(config)
+    }
+}
+
+/// Merges the configuration layers into a JSON value.
+///
+/// The layers are applied in order: the base file, overlay files,
+/// overrides from environment variables and at last the assignments
+/// in form of `path.to.field=value`.
+pub fn merge_layers<P>(base: P, overlays: &[PathBuf], assignments: &[String]) -> Fallible<Value>
+where
+    P: AsRef<Path>,
+{
+    let mut value = load_value(base)?;
+
+    for path in overlays.iter() {
+        merge_value(&mut value, load_value(path)?);
+    }
+
+    for (key, text) in std::env::vars() {
+        if let Some(name) = key.strip_prefix(ENV_PREFIX) {
+            let path = name
+                .split("__")
+                .map(|segment| segment.to_lowercase())
+                .collect::<Vec<_>>()
+                .join(".");
+            set_value(&mut value, &path, parse_field_value(&text))
+                .map_err(|err| failure::format_err!("invalid variable {}: {}", key, err))?;
+        }
+    }
+
+    for assignment in assignments.iter() {
+        let mut tokens = assignment.splitn(2, '=');
+        let path = tokens.next().unwrap();
+        let text = tokens
+            .next()
+            .ok_or_else(|| failure::format_err!("expect path=value, but get {:?}", assignment))?;
+        set_value(&mut value, path.trim(), parse_field_value(text))
+            .map_err(|err| failure::format_err!("invalid assignment {:?}: {}", assignment, err))?;
+    }
+
+    Ok(value)
+}
+
+/// Loads a configuration file into a JSON value.
+///
+/// The file can list base files in the `include` field, which are loaded
+/// before the file itself. Relative paths are resolved against the
+/// directory of the including file.
+pub fn load_value<P>(path: P) -> Fallible<Value>
+where
+    P: AsRef<Path>,
+{
+    load_value_recursive(path.as_ref(), &mut vec![])
+}
+
+fn load_value_recursive(path: &Path, stack: &mut Vec<PathBuf>) -> Fallible<Value> {
+    let canonical_path = path
+        .canonicalize()
+        .map_err(|err| failure::format_err!("failed to open {}: {}", path.display(), err))?;
+    if stack.contains(&canonical_path) {
+        failure::bail!("{} is included recursively", path.display());
+    }
+
+    let mut value: Value = {
         let mut reader = BufReader::new(File::open(path)?);
         let mut string = String::new();
         reader.read_to_string(&mut string)?;
-        let config: Self = json5::from_str(&string)?;
+        json5::from_str(&string)
+            .map_err(|err| failure::format_err!("failed to parse {}: {}", path.display(), err))?
+    };
+
+    let includes = match &mut value {
+        Value::Object(map) => match map.remove("include") {
+            None => vec![],
+            Some(Value::String(include)) => vec![include],
+            Some(Value::Array(includes)) => includes
+                .into_iter()
+                .map(|include| match include {
+                    Value::String(include) => Ok(include),
+                    _ => Err(failure::format_err!(
+                        "{}: include must be a list of paths",
+                        path.display()
+                    )),
+                })
+                .collect::<Fallible<Vec<_>>>()?,
+            Some(_) => failure::bail!("{}: include must be a list of paths", path.display()),
+        },
+        _ => failure::bail!("{}: configuration must be an object", path.display()),
+    };
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    stack.push(canonical_path);
+    let mut merged = Value::Object(Map::new());
+    for include in includes {
+        merge_value(
+            &mut merged,
+            load_value_recursive(&dir.join(include), stack)?,
+        );
+    }
+    stack.pop();
+
+    merge_value(&mut merged, value);
+    Ok(merged)
+}
+
+/// Merges the overlay into the base value. Objects are merged recursively,
+/// while other values in overlay replace the ones in base.
+pub fn merge_value(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base_map), Value::Object(overlay_map)) => {
+            for (key, overlay_value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(base_value) => merge_value(base_value, overlay_value),
+                    None => {
+                        base_map.insert(key, overlay_value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Sets the field at the dot-separated path. Array elements are addressed by indexes.
+pub fn set_value(root: &mut Value, path: &str, value: Value) -> Fallible<()> {
+    if path.is_empty() {
+        failure::bail!("empty field path");
+    }
+
+    let mut current = root;
+    for segment in path.split('.') {
+        current = match current {
+            Value::Object(map) => map
+                .entry(segment)
+                .or_insert_with(|| Value::Object(Map::new())),
+            Value::Array(array) => {
+                let len = array.len();
+                segment
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(move |index| array.get_mut(index))
+                    .ok_or_else(|| {
+                        failure::format_err!(
+                            "{} is not a valid index of array with {} elements",
+                            segment,
+                            len
+                        )
+                    })?
+            }
+            _ => failure::bail!("{} is not an object or array", path),
+        };
+    }
+    *current = value;
+
+    Ok(())
+}
+
+// Parses the value as JSON5, or takes it as a plain string otherwise.
+fn parse_field_value(text: &str) -> Value {
+    json5::from_str(text).unwrap_or_else(|_| Value::String(text.to_owned()))
+}
+
+impl ObjectDetectorConfig {
+    /// Loads and parses an object detector parameter file, where the
+    /// overrides take precedence over the fields in the file.
+    pub fn open<P>(path: P, overrides: &Map<String, Value>) -> Fallible<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut value = DetectorParams::load_value(path)?;
+        merge_value(&mut value, Value::Object(overrides.clone()));
+        let mut config: Self = serde_json::from_value(value)?;
+        config.params_file = Some(path.to_owned());
+        config.overrides = overrides.clone();
         Ok(config)
     }
 }
 
+fn default_playback_speed() -> f64 {
+    1.0
+}
+
+fn default_spatial_alpha() -> f32 {
+    0.5
+}
+
+fn default_spatial_iterations() -> usize {
+    2
+}
+
+fn default_temporal_alpha() -> f32 {
+    0.4
+}
+
+fn default_filter_delta() -> f32 {
+    0.02
+}
+
+fn default_segmentation_label() -> String {
+    String::from("depth")
+}
+
+fn default_plane_distance() -> f32 {
+    0.005
+}
+
+fn default_plane_iterations() -> usize {
+    200
+}
+
+fn default_sample_stride() -> usize {
+    4
+}
+
+fn default_max_extrapolation() -> f32 {
+    0.01
+}
+
+fn default_synthetic_fov() -> f32 {
+    69.4
+}
+
+fn default_recorder_dir() -> PathBuf {
+    PathBuf::from("recordings")
+}
+
 // This is custom deserializer for Format type.
 // See https://serde.rs/field-attrs.html
 fn deserialize_format<'de, D>(deserializer: D) -> Result<Format, D::Error>
@@ -130,53 +664,89 @@ where
     Ok(format)
 }
 
+// Loads the parameter file if given, and merges the other fields in the
+// configuration over it.
 fn deserialize_object_detector<'de, D>(deserializer: D) -> Result<ObjectDetectorConfig, D::Error>
 where
     D: Deserializer<'de>,
 {
-    let ObjectDetectorOrigConfig { params_file } =
-        ObjectDetectorOrigConfig::deserialize(deserializer)?;
+    let mut overrides = Map::<String, Value>::deserialize(deserializer)?;
+    let params_file = match overrides.remove("params_file") {
+        Some(Value::String(path)) => Some(PathBuf::from(path)),
+        Some(Value::Null) | None => None,
+        Some(value) => {
+            return Err(D::Error::custom(format!(
+                "params_file must be a path, but get {}",
+                value
+            )))
+        }
+    };
 
     let config = match params_file {
-        Some(path) => {
-            let config: ObjectDetectorConfig = {
-                let load_params = || {
-                    let mut reader = BufReader::new(File::open(path)?);
-                    let mut string = String::new();
-                    reader.read_to_string(&mut string)?;
-                    std::io::Result::Ok(string)
-                };
-
-                let string = load_params().map_err(|err| {
-                    D::Error::custom(format!(
-                        "failed to load object detector parameter file: {:?}",
-                        err
-                    ))
-                })?;
-                json5::from_str(&string).map_err(|err| {
-                    D::Error::custom(format!(
-                        "invalid object detector paramter file format: {:?}",
-                        err
-                    ))
-                })?
-            };
-            config
-        }
-        None => ObjectDetectorConfig {
-            inversion: None,
-            blur_kernel: None,
-            n_dilations: None,
-            dilation_kernel: None,
-            n_erosions: None,
-            erosion_kernel: None,
-            n_objects: None,
-            min_arc_length: None,
-            max_arc_length: None,
-            roi: None,
-            lower_bound: None,
-            upper_bound: None,
-        },
+        Some(path) => ObjectDetectorConfig::open(&path, &overrides).map_err(|err| {
+            D::Error::custom(format!(
+                "failed to load object detector parameter file {}: {:?}",
+                path.display(),
+                err
+            ))
+        })?,
+        None => serde_json::from_value(Value::Object(overrides)).map_err(D::Error::custom)?,
     };
 
     Ok(config)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge_and_set_values() -> Fallible<()> {
+        let mut value = json!({
+            "dobot": { "enabled": true, "device": "/dev/ttyUSB0" },
+            "controller": { "translation": [0.0, 0.0], "depth_robot": [1.0, 2.0] },
+        });
+        merge_value(
+            &mut value,
+            json!({ "dobot": { "enabled": false }, "controller": { "translation": [1.0] } }),
+        );
+        set_value(&mut value, "controller.depth_robot.1", json!(3.0))?;
+        set_value(&mut value, "recorder.enabled", parse_field_value("true"))?;
+        set_value(&mut value, "recorder.dir", parse_field_value("/tmp/rec"))?;
+
+        assert_eq!(
+            value,
+            json!({
+                "dobot": { "enabled": false, "device": "/dev/ttyUSB0" },
+                "controller": { "translation": [1.0], "depth_robot": [1.0, 3.0] },
+                "recorder": { "enabled": true, "dir": "/tmp/rec" },
+            })
+        );
+        assert!(set_value(&mut value, "controller.depth_robot.2", json!(0.0)).is_err());
+        assert!(set_value(&mut value, "dobot.enabled.value", json!(0.0)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn override_object_detector_params() -> Fallible<()> {
+        let path =
+            std::env::temp_dir().join(format!("hacky-arm-params-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            "{ blur_kernel: 7, n_objects: 3, // comment\n min_depth_pixels: 10, }",
+        )?;
+
+        let mut value = json!({ "params_file": path });
+        set_value(&mut value, "blur_kernel", json!(9))?;
+        let config = deserialize_object_detector(value);
+        std::fs::remove_file(&path)?;
+
+        let config = config?;
+        assert_eq!(config.detector.blur_kernel, Some(9));
+        assert_eq!(config.detector.n_objects, Some(3));
+        assert_eq!(config.min_depth_pixels, Some(10));
+        assert_eq!(config.overrides.get("blur_kernel"), Some(&json!(9)));
+        Ok(())
+    }
+}
@@ -1,7 +1,10 @@
 use crate::{
-    config::Config,
+    config::{Config, ControllerConfig},
+    depth_model::{DepthModel, RobotZ},
+    grasp,
     message::{ControlMessage, DetectorMessage, DobotMessage, VisualizerMessage},
     object_detector::Object,
+    stability::StabilityGate,
     state::GlobalState,
     utils::WatchedObject,
 };
@@ -17,6 +20,9 @@ use tokio::{sync::broadcast, task::JoinHandle};
 #[derive(Debug)]
 struct ControllerCache {
     pub detector_msg: Option<Arc<DetectorMessage>>,
+    pub stability: StabilityGate,
+    /// the target that is stable enough to grab, with the averaged pose
+    pub stable_target: Option<Object>,
 }
 
 pub struct Controller {
@@ -38,7 +44,11 @@ impl Controller {
         state: WatchedObject<GlobalState>,
     ) -> Fallible<ControllerHandle> {
         let spawn_handle = tokio::spawn(async move {
-            let cache = ControllerCache { detector_msg: None };
+            let cache = ControllerCache {
+                detector_msg: None,
+                stability: StabilityGate::new(config.controller.stability),
+                stable_target: None,
+            };
             let controller = Controller {
                 config,
                 detector_msg_rx,
@@ -73,8 +83,24 @@ impl Controller {
                             Err(broadcast::RecvError::Closed) => break,
                         };
 
+                        // frames taken while the arm moves are not reliable
+                        let is_dobot_busy = self.state.read().await.is_dobot_busy;
+
                         // self.cache.detector_msg = Some(msg);
                         let mut cache = self.cache.lock().unwrap();
+                        if is_dobot_busy {
+                            cache.stability.reset();
+                            cache.stable_target = None;
+                        } else {
+                            let targets = msg
+                                .detection
+                                .objects
+                                .iter()
+                                .filter(|obj| is_target(&self.config, obj))
+                                .map(|obj| obj.as_ref())
+                                .collect::<Vec<_>>();
+                            cache.stable_target = cache.stability.update(&targets);
+                        }
                         cache.detector_msg = Some(msg);
                     }
                     result = self.control_rx.recv() => {
@@ -111,6 +137,16 @@ impl Controller {
                                     info!("auto grabbing enabled");
                                 }
                             }
+                            ControlMessage::ToggleRecording => {
+                                let mut state = self.state.write().await;
+                                let prev = state.is_recording;
+                                state.is_recording = !prev;
+                                if prev {
+                                    info!("recording disabled");
+                                } else {
+                                    info!("recording enabled");
+                                }
+                            }
                         }
                     }
                 }
@@ -130,7 +166,12 @@ impl Controller {
         let mut cache = self.cache.lock().unwrap();
 
         if let Some(msg) = cache.detector_msg.take() {
-            match msg.detection.objects.first() {
+            match msg
+                .detection
+                .objects
+                .iter()
+                .find(|obj| is_target(&self.config, obj))
+            {
                 Some(obj) => {
                     let dobot_msg = DobotMessage::GrabObject(obj.clone());
                     if let Err(_) = dobot_tx.send((dobot_msg, Instant::now())) {
@@ -168,6 +209,7 @@ impl Controller {
         let (dobot_tx, mut dobot_rx) = broadcast::channel(1);
         let config = self.config.clone();
         let state = self.state.clone();
+        let depth_model = DepthModel::fit(&config.controller)?;
 
         let handle = tokio::spawn(async move {
             info!("dobot worker started");
@@ -216,35 +258,26 @@ impl Controller {
                                 continue;
                             }
 
-                            let [[a00, a01], [a10, a11]] = config.controller.linear_transform;
-                            let [b0, b1] = config.controller.translation;
-
                             let (x, mut y, angle, depth) = {
                                 let Object {
                                     x, y, angle, depth, ..
                                 } = *obj;
-                                let x = x as f64;
-                                let y = y as f64;
-                                let pos_x = a00 * x + a01 * y + b0;
-                                let pos_y = a10 * x + a11 * y + b1;
-                                (pos_x as f32, pos_y as f32, angle, depth)
+                                let (pos_x, pos_y) = pixel_to_robot(&config.controller, x, y);
+                                (pos_x, pos_y, angle, depth)
                             };
 
-                            let depth_range = config.controller.depth_image;
-                            let depth_robot = config.controller.depth_robot;
-
-                            let z: f32 = {
-                                let mut z: f32 = 0.;
-                                for i in 0..(depth_range.len() - 1) {
-                                    if depth > (depth_range[i] + depth_range[i + 1]) / 2. {
-                                        z = depth_robot[i];
-                                        break;
-                                    }
-                                }
-                                if z == 0. {
-                                    z = depth_robot[depth_range.len() - 1];
+                            let z = match depth_model.evaluate(depth) {
+                                RobotZ {
+                                    z, in_range: true, ..
+                                } => z,
+                                RobotZ { .. } => {
+                                    let (min_depth, max_depth) = depth_model.depth_range();
+                                    warn!(
+                                        "depth {:.3}(m) is out of calibrated range [{:.3}, {:.3}]",
+                                        depth, min_depth, max_depth
+                                    );
+                                    continue;
                                 }
-                                z
                             };
 
                             let facing = state.read().await.facing;
@@ -254,21 +287,45 @@ impl Controller {
                                 y = -y;
                             }
 
+                            // take the nearest rotation within joint 4 limits, where
+                            // move_to() turns the arm by -90(deg) if not facing
+                            let r = {
+                                let (arm_x, arm_y, turn) = if facing {
+                                    (x, y, 0.)
+                                } else {
+                                    (-y, -x, -90.)
+                                };
+                                let base_angle = arm_y.atan2(arm_x).to_degrees();
+                                match grasp::normalize_rotation(
+                                    angle + home.3 + turn,
+                                    base_angle,
+                                    home.3 + turn,
+                                    config.controller.gripper.joint4_limits,
+                                ) {
+                                    Some(r) => r - turn,
+                                    None => {
+                                        warn!(
+                                            "angle {:.1}(deg) is out of joint 4 limits {:?}",
+                                            angle, config.controller.gripper.joint4_limits
+                                        );
+                                        continue;
+                                    }
+                                }
+                            };
+
                             // move to target position
                             dobot.release().await?.wait().await?;
-                            dobot =
-                                move_to(dobot, facing, x, y, home.2 - 70., angle + home.3).await?;
+                            dobot = move_to(dobot, facing, x, y, home.2 - 70., r).await?;
 
                             // go down
-                            dobot = move_to(dobot, facing, x, y, z, angle + 9.0).await?;
+                            dobot = move_to(dobot, facing, x, y, z, r).await?;
 
                             // grip
                             dobot.grip().await?.wait().await?;
                             tokio::time::delay_for(Duration::from_secs(1)).await;
 
                             // lift up
-                            dobot =
-                                move_to(dobot, facing, x, y, home.2 - 110., angle + home.3).await?;
+                            dobot = move_to(dobot, facing, x, y, home.2 - 110., r).await?;
 
                             // rotate 45(deg) clockwisely
                             dobot = move_to(dobot, facing, 196., -160., 50.0, home.3).await?;
@@ -370,6 +427,7 @@ impl Controller {
         &self,
         dobot_tx: broadcast::Sender<(DobotMessage, Instant)>,
     ) -> Fallible<JoinHandle<Fallible<()>>> {
+        let config = self.config.clone();
         let state = self.state.clone();
         let cache_mutex = self.cache.clone();
 
@@ -383,19 +441,29 @@ impl Controller {
                     continue;
                 }
 
-                let detector_msg = {
+                let (detector_msg, stable_target) = {
                     let mut cache = cache_mutex.lock().unwrap();
-                    cache.detector_msg.take()
+                    (cache.detector_msg.take(), cache.stable_target.take())
                 };
                 if let Some(msg) = detector_msg {
-                    match msg.detection.objects.first() {
+                    let has_target = msg
+                        .detection
+                        .objects
+                        .iter()
+                        .any(|obj| is_target(&config, obj));
+                    match stable_target {
                         Some(obj) => {
                             counter = 0;
-                            let dobot_msg = DobotMessage::GrabObject(obj.clone());
+                            // observe the scene again for the next grasp
+                            cache_mutex.lock().unwrap().stability.reset();
+                            let dobot_msg = DobotMessage::GrabObject(Arc::new(obj));
                             if let Err(_) = dobot_tx.send((dobot_msg, Instant::now())) {
                                 break;
                             }
                         }
+                        None if has_target => {
+                            // wait until the target settles
+                        }
                         None => {
                             if !state.read().await.is_dobot_busy {
                                 counter += 1;
@@ -423,6 +491,23 @@ impl Controller {
     }
 }
 
+/// Maps the pixel to robot x and y in millimeters by the calibrated affine transformation.
+pub fn pixel_to_robot(config: &ControllerConfig, x: i32, y: i32) -> (f32, f32) {
+    let [[a00, a01], [a10, a11]] = config.linear_transform;
+    let [b0, b1] = config.translation;
+    let x = x as f64;
+    let y = y as f64;
+    let pos_x = a00 * x + a01 * y + b0;
+    let pos_y = a10 * x + a11 * y + b1;
+    (pos_x as f32, pos_y as f32)
+}
+
+/// Checks if the object has valid depth, fits in the gripper and is of a target class.
+fn is_target(config: &Config, obj: &Object) -> bool {
+    let targets = &config.controller.target_labels;
+    obj.depth_valid && obj.graspable && (targets.is_empty() || targets.contains(&obj.label))
+}
+
 #[derive(Debug)]
 pub struct ControllerHandle {
     pub handle: JoinHandle<Fallible<()>>,
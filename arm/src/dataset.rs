@@ -0,0 +1,236 @@
+//! The on-disk format of recorded RealSense sessions.
+//!
+//! A session is a directory of frames. Each frame is stored in four files
+//! sharing the same zero-padded index as file stem.
+//!
+//! - `<index>.json`: the frame metadata
+//! - `<index>-color.png`: the RGB8 color image
+//! - `<index>-depth.png`: the raw Z16 depth image in 16-bit grayscale
+//! - `<index>-points.bin`: the point cloud, stored as little-endian `f32`
+//!   quintuples `(x, y, z, u, v)` of positions and texture coordinates
+
+use crate::{
+    frame_source::{DepthImage, Intrinsics},
+    message::RealSenseMessage,
+};
+use failure::Fallible;
+use image::DynamicImage;
+use nalgebra::{Point2, Point3};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{prelude::*, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+const POINT_SIZE: usize = 5 * std::mem::size_of::<f32>();
+
+/// The metadata of a recorded frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameMeta {
+    pub timestamp: Duration,
+    pub depth_scale: f32,
+    pub n_points: usize,
+    pub intrinsics: Intrinsics,
+}
+
+/// Lists the frames in a session directory in recording order.
+pub fn list_frames<P>(dir: P) -> Fallible<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<std::io::Result<Vec<_>>>()?
+        .into_iter()
+        .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Loads a frame given the path to its metadata file.
+pub fn read_frame<P>(meta_path: P) -> Fallible<RealSenseMessage>
+where
+    P: AsRef<Path>,
+{
+    let meta_path = meta_path.as_ref();
+    let FrameMeta {
+        timestamp,
+        depth_scale,
+        n_points,
+        intrinsics,
+    } = serde_json::from_reader(BufReader::new(File::open(meta_path)?))?;
+
+    let color_image = image::open(sibling_path(meta_path, "color.png")?)?.to_rgb8();
+    let depth_image = match image::open(sibling_path(meta_path, "depth.png")?)? {
+        DynamicImage::ImageLuma16(image) => image,
+        _ => failure::bail!("depth image of {} is not 16-bit", meta_path.display()),
+    };
+
+    let (points, texture_coordinates) = {
+        let mut bytes = vec![];
+        File::open(sibling_path(meta_path, "points.bin")?)?.read_to_end(&mut bytes)?;
+        if bytes.len() != n_points * POINT_SIZE {
+            failure::bail!(
+                "expect {} points in {}, but found {} bytes",
+                n_points,
+                meta_path.display(),
+                bytes.len()
+            );
+        }
+
+        bytes
+            .chunks_exact(POINT_SIZE)
+            .map(|chunk| {
+                let mut values = chunk.chunks_exact(4).map(|sample| {
+                    let mut buf = [0u8; 4];
+                    buf.copy_from_slice(sample);
+                    f32::from_le_bytes(buf)
+                });
+                let mut next = || values.next().unwrap();
+                let point = Point3::new(next(), next(), next());
+                let texture_coordinate = Point2::new(next(), next());
+                (point, texture_coordinate)
+            })
+            .unzip::<_, _, Vec<_>, Vec<_>>()
+    };
+
+    let msg = RealSenseMessage {
+        timestamp,
+        color_image: Arc::new(color_image),
+        depth_image: Arc::new(DepthImage {
+            image: depth_image,
+            scale: depth_scale,
+        }),
+        points: Arc::new(points),
+        texture_coordinates: Arc::new(texture_coordinates),
+        intrinsics,
+        ground_truth: None,
+    };
+    Ok(msg)
+}
+
+/// Saves a frame to the session directory with the given frame index.
+pub fn write_frame<P>(dir: P, index: usize, msg: &RealSenseMessage) -> Fallible<()>
+where
+    P: AsRef<Path>,
+{
+    let RealSenseMessage {
+        timestamp,
+        color_image,
+        depth_image,
+        points,
+        texture_coordinates,
+        intrinsics,
+        ..
+    } = msg;
+
+    if points.len() != texture_coordinates.len() {
+        failure::bail!(
+            "the number of points ({}) and texture coordinates ({}) mismatch",
+            points.len(),
+            texture_coordinates.len()
+        );
+    }
+
+    let meta_path = dir.as_ref().join(format!("{:06}.json", index));
+
+    color_image.save(sibling_path(&meta_path, "color.png")?)?;
+    depth_image
+        .image
+        .save(sibling_path(&meta_path, "depth.png")?)?;
+
+    {
+        let mut writer = BufWriter::new(File::create(sibling_path(&meta_path, "points.bin")?)?);
+        for (point, texture_coordinate) in points.iter().zip(texture_coordinates.iter()) {
+            let values = [
+                point.x,
+                point.y,
+                point.z,
+                texture_coordinate.x,
+                texture_coordinate.y,
+            ];
+            for value in values.iter() {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        writer.flush()?;
+    }
+
+    // write metadata at last, so that incomplete frames are not listed
+    let meta = FrameMeta {
+        timestamp: *timestamp,
+        depth_scale: depth_image.scale,
+        n_points: points.len(),
+        intrinsics: *intrinsics,
+    };
+    serde_json::to_writer_pretty(BufWriter::new(File::create(&meta_path)?), &meta)?;
+
+    Ok(())
+}
+
+// Computes the path of a file of the same frame, e.g. "000001.json" -> "000001-color.png".
+fn sibling_path(meta_path: &Path, suffix: &str) -> Fallible<PathBuf> {
+    let stem = meta_path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| failure::format_err!("invalid frame path {}", meta_path.display()))?;
+    Ok(meta_path.with_file_name(format!("{}-{}", stem, suffix)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Luma, Rgb};
+
+    #[test]
+    fn write_and_read_frame() -> Fallible<()> {
+        let dir = std::env::temp_dir().join(format!("hacky-arm-dataset-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let msg = RealSenseMessage {
+            timestamp: Duration::from_millis(1234),
+            color_image: Arc::new(ImageBuffer::from_fn(4, 3, |x, y| {
+                Rgb([x as u8, y as u8, 255])
+            })),
+            depth_image: Arc::new(DepthImage {
+                image: ImageBuffer::from_fn(4, 3, |x, y| Luma([(x * 100 + y) as u16])),
+                scale: 0.001,
+            }),
+            points: Arc::new(vec![
+                Point3::new(0.1, -0.2, 0.3),
+                Point3::new(1.0, 2.0, 3.0),
+            ]),
+            texture_coordinates: Arc::new(vec![Point2::new(0.5, 0.25), Point2::new(0.0, 1.0)]),
+            intrinsics: Intrinsics {
+                width: 4,
+                height: 3,
+                ppx: 2.0,
+                ppy: 1.5,
+                fx: 600.0,
+                fy: 600.0,
+            },
+            ground_truth: None,
+        };
+        write_frame(&dir, 0, &msg)?;
+        write_frame(&dir, 1, &msg)?;
+
+        let frames = list_frames(&dir)?;
+        assert_eq!(frames.len(), 2);
+
+        let loaded = read_frame(&frames[1])?;
+        assert_eq!(loaded.timestamp, msg.timestamp);
+        assert_eq!(*loaded.color_image, *msg.color_image);
+        assert_eq!(loaded.depth_image.image, msg.depth_image.image);
+        assert_eq!(loaded.depth_image.scale, msg.depth_image.scale);
+        assert_eq!(loaded.points, msg.points);
+        assert_eq!(loaded.texture_coordinates, msg.texture_coordinates);
+        assert_eq!(loaded.intrinsics, msg.intrinsics);
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+use crate::{
+    config::{DepthFilterConfig, HoleFillingMode},
+    frame_source::{DepthImage, Intrinsics},
+    message::RealSenseMessage,
+};
+use image::{ImageBuffer, Luma};
+use nalgebra::{Point2, Point3};
+use std::sync::Arc;
+
+/// The depth in meters in row-major order, where zeros mark holes.
+#[derive(Debug, Clone)]
+struct DepthMap {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl DepthMap {
+    fn from_image(image: &DepthImage) -> Self {
+        let data = image
+            .image
+            .pixels()
+            .map(|pixel| {
+                let Luma([value]) = *pixel;
+                value as f32 * image.scale
+            })
+            .collect();
+        Self {
+            width: image.width(),
+            height: image.height(),
+            data,
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    fn set(&mut self, x: usize, y: usize, value: f32) {
+        self.data[y * self.width + x] = value;
+    }
+
+    // Resizes the map to the given size by nearest neighbor sampling.
+    fn resize(&self, width: usize, height: usize) -> Self {
+        if width == self.width && height == self.height {
+            return self.clone();
+        }
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let src_y = (y * self.height / height).min(self.height - 1);
+            for x in 0..width {
+                let src_x = (x * self.width / width).min(self.width - 1);
+                data.push(self.get(src_x, src_y));
+            }
+        }
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum DepthFilter {
+    Decimation {
+        factor: usize,
+    },
+    Spatial {
+        alpha: f32,
+        delta: f32,
+        iterations: usize,
+    },
+    Temporal {
+        alpha: f32,
+        delta: f32,
+        previous: Option<DepthMap>,
+    },
+    HoleFilling {
+        mode: HoleFillingMode,
+    },
+}
+
+/// The chain of software depth filters.
+///
+/// It filters the depth image of RealSense messages and recomputes the
+/// point cloud from the filtered depth. Since it only relies on the message
+/// content, it works the same on live, recorded and synthetic frames.
+#[derive(Debug)]
+pub struct DepthFilterChain {
+    filters: Vec<DepthFilter>,
+}
+
+impl DepthFilterChain {
+    pub fn new(configs: &[DepthFilterConfig]) -> Self {
+        let filters = configs
+            .iter()
+            .map(|config| match *config {
+                DepthFilterConfig::Decimation { factor } => DepthFilter::Decimation { factor },
+                DepthFilterConfig::Spatial {
+                    alpha,
+                    delta,
+                    iterations,
+                } => DepthFilter::Spatial {
+                    alpha,
+                    delta,
+                    iterations,
+                },
+                DepthFilterConfig::Temporal { alpha, delta } => DepthFilter::Temporal {
+                    alpha,
+                    delta,
+                    previous: None,
+                },
+                DepthFilterConfig::HoleFilling { mode } => DepthFilter::HoleFilling { mode },
+            })
+            .collect();
+        Self { filters }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Filters the depth image and recomputes the point cloud of the message.
+    pub fn process(&mut self, msg: &RealSenseMessage) -> RealSenseMessage {
+        let RealSenseMessage {
+            timestamp,
+            color_image,
+            depth_image,
+            intrinsics,
+            ground_truth,
+            ..
+        } = msg;
+        let scale = depth_image.scale;
+
+        let input = DepthMap::from_image(depth_image);
+        let mut map = input.clone();
+        let mut factor = 1;
+
+        for filter in self.filters.iter_mut() {
+            map = match filter {
+                DepthFilter::Decimation { factor: step } => {
+                    factor *= *step;
+                    decimate(&map, *step)
+                }
+                DepthFilter::Spatial {
+                    alpha,
+                    delta,
+                    iterations,
+                } => spatial_filter(map, *alpha, *delta, *iterations),
+                DepthFilter::Temporal {
+                    alpha,
+                    delta,
+                    previous,
+                } => {
+                    let filtered = temporal_filter(map, previous.as_ref(), *alpha, *delta);
+                    *previous = Some(filtered.clone());
+                    filtered
+                }
+                DepthFilter::HoleFilling { mode } => fill_holes(&map, *mode),
+            };
+        }
+
+        let (points, texture_coordinates) =
+            compute_points(&map, &scale_intrinsics(intrinsics, factor));
+
+        // restore the resolution so that depth pixels match color pixels
+        let depth_image = {
+            let restored = map.resize(input.width, input.height);
+            let samples = restored
+                .data
+                .iter()
+                .map(|&depth| {
+                    if scale > 0.0 {
+                        (depth / scale).round().min(u16::MAX as f32) as u16
+                    } else {
+                        0
+                    }
+                })
+                .collect::<Vec<_>>();
+            let image =
+                ImageBuffer::from_raw(input.width as u32, input.height as u32, samples).unwrap();
+            DepthImage { image, scale }
+        };
+
+        RealSenseMessage {
+            timestamp: *timestamp,
+            color_image: Arc::clone(color_image),
+            depth_image: Arc::new(depth_image),
+            points: Arc::new(points),
+            texture_coordinates: Arc::new(texture_coordinates),
+            intrinsics: *intrinsics,
+            ground_truth: ground_truth.clone(),
+        }
+    }
+}
+
+// Downsamples the map by taking the median of valid pixels in each block.
+fn decimate(map: &DepthMap, factor: usize) -> DepthMap {
+    if factor <= 1 {
+        return map.clone();
+    }
+
+    let width = (map.width + factor - 1) / factor;
+    let height = (map.height + factor - 1) / factor;
+    let mut data = Vec::with_capacity(width * height);
+    let mut block = Vec::with_capacity(factor * factor);
+
+    for by in 0..height {
+        for bx in 0..width {
+            block.clear();
+            for y in (by * factor)..((by + 1) * factor).min(map.height) {
+                for x in (bx * factor)..((bx + 1) * factor).min(map.width) {
+                    let depth = map.get(x, y);
+                    if depth > 0.0 {
+                        block.push(depth);
+                    }
+                }
+            }
+
+            let depth = if block.is_empty() {
+                0.0
+            } else {
+                block.sort_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap());
+                block[block.len() / 2]
+            };
+            data.push(depth);
+        }
+    }
+
+    DepthMap {
+        width,
+        height,
+        data,
+    }
+}
+
+// Edge-preserving smoothing by recursive exponential filters, which sweep
+// rows and columns in both directions. Neighbors are blended only if their
+// difference is below delta, so object edges are kept sharp.
+fn spatial_filter(mut map: DepthMap, alpha: f32, delta: f32, iterations: usize) -> DepthMap {
+    let blend = |prev: f32, curr: f32| {
+        if prev > 0.0 && curr > 0.0 && (curr - prev).abs() < delta {
+            alpha * curr + (1.0 - alpha) * prev
+        } else {
+            curr
+        }
+    };
+
+    for _ in 0..iterations {
+        for y in 0..map.height {
+            for x in 1..map.width {
+                let value = blend(map.get(x - 1, y), map.get(x, y));
+                map.set(x, y, value);
+            }
+            for x in (0..(map.width.saturating_sub(1))).rev() {
+                let value = blend(map.get(x + 1, y), map.get(x, y));
+                map.set(x, y, value);
+            }
+        }
+        for x in 0..map.width {
+            for y in 1..map.height {
+                let value = blend(map.get(x, y - 1), map.get(x, y));
+                map.set(x, y, value);
+            }
+            for y in (0..(map.height.saturating_sub(1))).rev() {
+                let value = blend(map.get(x, y + 1), map.get(x, y));
+                map.set(x, y, value);
+            }
+        }
+    }
+
+    map
+}
+
+// Blends the current frame with the previous filtered frame pixel by pixel.
+fn temporal_filter(
+    mut map: DepthMap,
+    previous: Option<&DepthMap>,
+    alpha: f32,
+    delta: f32,
+) -> DepthMap {
+    let previous = match previous {
+        Some(previous) if previous.width == map.width && previous.height == map.height => previous,
+        _ => return map,
+    };
+
+    for (curr, &prev) in map.data.iter_mut().zip(previous.data.iter()) {
+        if *curr > 0.0 && prev > 0.0 && (*curr - prev).abs() < delta {
+            *curr = alpha * *curr + (1.0 - alpha) * prev;
+        }
+    }
+
+    map
+}
+
+// Fills holes from neighbor pixels of the input map.
+fn fill_holes(map: &DepthMap, mode: HoleFillingMode) -> DepthMap {
+    let mut output = map.clone();
+
+    match mode {
+        HoleFillingMode::FillFromLeft => {
+            for y in 0..map.height {
+                for x in 1..map.width {
+                    if output.get(x, y) <= 0.0 {
+                        let left = output.get(x - 1, y);
+                        output.set(x, y, left);
+                    }
+                }
+            }
+        }
+        HoleFillingMode::FarthestFromAround | HoleFillingMode::NearestFromAround => {
+            for y in 0..map.height {
+                for x in 0..map.width {
+                    if map.get(x, y) > 0.0 {
+                        continue;
+                    }
+
+                    let neighbors = [
+                        (x.wrapping_sub(1), y),
+                        (x + 1, y),
+                        (x, y.wrapping_sub(1)),
+                        (x, y + 1),
+                    ];
+                    let valid = neighbors
+                        .iter()
+                        .filter(|&&(nx, ny)| nx < map.width && ny < map.height)
+                        .map(|&(nx, ny)| map.get(nx, ny))
+                        .filter(|&depth| depth > 0.0);
+                    let fill = match mode {
+                        HoleFillingMode::FarthestFromAround => valid.fold(0.0, f32::max),
+                        _ => valid.fold(f32::INFINITY, f32::min),
+                    };
+                    if fill.is_finite() {
+                        output.set(x, y, fill);
+                    }
+                }
+            }
+        }
+    }
+
+    output
+}
+
+// Adjusts the intrinsics for a map downsampled by the factor.
+fn scale_intrinsics(intrinsics: &Intrinsics, factor: usize) -> Intrinsics {
+    if factor <= 1 {
+        return *intrinsics;
+    }
+    let Intrinsics {
+        width,
+        height,
+        ppx,
+        ppy,
+        fx,
+        fy,
+    } = *intrinsics;
+    let factor_f32 = factor as f32;
+    Intrinsics {
+        width: (width + factor - 1) / factor,
+        height: (height + factor - 1) / factor,
+        ppx: (ppx + 0.5) / factor_f32 - 0.5,
+        ppy: (ppy + 0.5) / factor_f32 - 0.5,
+        fx: fx / factor_f32,
+        fy: fy / factor_f32,
+    }
+}
+
+// Deprojects each depth pixel into a point. Holes become points at origin
+// as librealsense does.
+fn compute_points(map: &DepthMap, intrinsics: &Intrinsics) -> (Vec<Point3<f32>>, Vec<Point2<f32>>) {
+    let mut points = Vec::with_capacity(map.width * map.height);
+    let mut texture_coordinates = Vec::with_capacity(map.width * map.height);
+
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let depth = map.get(x, y);
+            let point = if depth > 0.0 {
+                intrinsics.deproject(x as f32, y as f32, depth)
+            } else {
+                Point3::origin()
+            };
+            points.push(point);
+            texture_coordinates.push(Point2::new(
+                (x as f32 + 0.5) / map.width as f32,
+                (y as f32 + 0.5) / map.height as f32,
+            ));
+        }
+    }
+
+    (points, texture_coordinates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(width: usize, data: &[f32]) -> DepthMap {
+        DepthMap {
+            width,
+            height: data.len() / width,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn decimation_takes_block_median() {
+        let input = map(4, &[1.0, 2.0, 0.0, 0.0, 3.0, 4.0, 0.0, 5.0]);
+        let output = decimate(&input, 2);
+        assert_eq!((output.width, output.height), (2, 1));
+        assert_eq!(output.data, vec![3.0, 5.0]);
+    }
+
+    #[test]
+    fn spatial_filter_preserves_edges() {
+        let input = map(4, &[1.0, 1.1, 2.0, 2.0]);
+        let output = spatial_filter(input, 0.5, 0.2, 1);
+        assert!(output.data[0] > 1.0 && output.data[1] < 1.1);
+        assert_eq!(&output.data[2..], &[2.0, 2.0]);
+    }
+
+    #[test]
+    fn hole_filling() {
+        let input = map(3, &[1.0, 0.0, 2.0]);
+        assert_eq!(
+            fill_holes(&input, HoleFillingMode::FillFromLeft).data,
+            vec![1.0, 1.0, 2.0]
+        );
+        assert_eq!(
+            fill_holes(&input, HoleFillingMode::FarthestFromAround).data,
+            vec![1.0, 2.0, 2.0]
+        );
+        assert_eq!(
+            fill_holes(&input, HoleFillingMode::NearestFromAround).data,
+            vec![1.0, 1.0, 2.0]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use crate::config::{ControllerConfig, DepthModelConfig};
+use failure::{format_err, Fallible};
+use nalgebra::{DMatrix, DVector};
+
+/// The robot height evaluated from an object depth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RobotZ {
+    pub z: f32,
+    /// true if the depth is within the calibrated range plus the extrapolation margin
+    pub in_range: bool,
+    /// true if z is clamped to the limits
+    pub clamped: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Curve {
+    /// calibration pairs sorted by depth
+    PiecewiseLinear(Vec<(f32, f32)>),
+    /// coefficients in increasing order of normalized depth (depth - mean) / scale
+    Polynomial {
+        coefficients: Vec<f64>,
+        mean: f64,
+        scale: f64,
+    },
+}
+
+/// The continuous model that maps object depth in meters to robot z,
+/// which is fitted from calibration pairs.
+#[derive(Debug, Clone)]
+pub struct DepthModel {
+    curve: Curve,
+    depth_range: (f32, f32),
+    z_limits: (f32, f32),
+    max_extrapolation: f32,
+}
+
+impl DepthModel {
+    /// Fits the model from the calibration table of the controller configuration.
+    pub fn fit(config: &ControllerConfig) -> Fallible<Self> {
+        let ControllerConfig {
+            depth_image,
+            depth_robot,
+            depth_model,
+            z_limits,
+            max_extrapolation,
+            ..
+        } = config;
+
+        if depth_image.is_empty() || depth_image.len() != depth_robot.len() {
+            failure::bail!("depth_image and depth_robot must be non-empty and of equal length");
+        }
+
+        let mut pairs = depth_image
+            .iter()
+            .cloned()
+            .zip(depth_robot.iter().cloned())
+            .collect::<Vec<_>>();
+        pairs.sort_by(|(lhs, _), (rhs, _)| lhs.partial_cmp(rhs).unwrap());
+
+        let depth_range = (pairs[0].0, pairs[pairs.len() - 1].0);
+        let z_limits = match z_limits {
+            Some([min, max]) => (*min, *max),
+            None => pairs
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), (_, z)| {
+                    (min.min(*z), max.max(*z))
+                }),
+        };
+
+        let curve = match *depth_model {
+            DepthModelConfig::PiecewiseLinear => Curve::PiecewiseLinear(pairs),
+            DepthModelConfig::Polynomial { degree } => fit_polynomial(&pairs, degree)?,
+        };
+
+        Ok(Self {
+            curve,
+            depth_range,
+            z_limits,
+            max_extrapolation: *max_extrapolation,
+        })
+    }
+
+    /// Evaluates the robot z at the depth in meters.
+    pub fn evaluate(&self, depth: f32) -> RobotZ {
+        let z = match &self.curve {
+            Curve::PiecewiseLinear(pairs) => interpolate(pairs, depth),
+            Curve::Polynomial {
+                coefficients,
+                mean,
+                scale,
+            } => {
+                let x = (depth as f64 - mean) / scale;
+                coefficients
+                    .iter()
+                    .rev()
+                    .fold(0.0, |sum, coef| sum * x + coef) as f32
+            }
+        };
+
+        let (min_depth, max_depth) = self.depth_range;
+        let in_range = depth.is_finite()
+            && depth >= min_depth - self.max_extrapolation
+            && depth <= max_depth + self.max_extrapolation;
+
+        let (min_z, max_z) = self.z_limits;
+        let clamped_z = z.max(min_z).min(max_z);
+
+        RobotZ {
+            z: clamped_z,
+            in_range,
+            clamped: clamped_z != z,
+        }
+    }
+
+    /// The calibrated depth range in meters.
+    pub fn depth_range(&self) -> (f32, f32) {
+        self.depth_range
+    }
+}
+
+// Interpolates linearly between neighboring pairs, and extrapolates
+// with the slopes at both ends.
+fn interpolate(pairs: &[(f32, f32)], depth: f32) -> f32 {
+    if pairs.len() == 1 {
+        return pairs[0].1;
+    }
+
+    let index = pairs
+        .windows(2)
+        .position(|pair| depth <= pair[1].0)
+        .unwrap_or(pairs.len() - 2);
+    let (d0, z0) = pairs[index];
+    let (d1, z1) = pairs[index + 1];
+    z0 + (z1 - z0) * (depth - d0) / (d1 - d0)
+}
+
+// Fits the polynomial by least squares on normalized depths.
+fn fit_polynomial(pairs: &[(f32, f32)], degree: usize) -> Fallible<Curve> {
+    if pairs.len() <= degree {
+        failure::bail!(
+            "fitting a polynomial of degree {} requires more than {} calibration pairs",
+            degree,
+            degree
+        );
+    }
+
+    let n_pairs = pairs.len();
+    let mean = pairs.iter().map(|(depth, _)| *depth as f64).sum::<f64>() / n_pairs as f64;
+    let scale = pairs
+        .iter()
+        .map(|(depth, _)| (*depth as f64 - mean).abs())
+        .fold(0.0, f64::max)
+        .max(1e-6);
+
+    let vandermonde = DMatrix::from_fn(n_pairs, degree + 1, |row, col| {
+        ((pairs[row].0 as f64 - mean) / scale).powi(col as i32)
+    });
+    let targets = DVector::from_iterator(n_pairs, pairs.iter().map(|(_, z)| *z as f64));
+    let solution = vandermonde
+        .svd(true, true)
+        .solve(&targets, 1e-12)
+        .map_err(|err| format_err!("failed to fit depth model: {}", err))?;
+
+    Ok(Curve::Polynomial {
+        coefficients: solution.iter().cloned().collect(),
+        mean,
+        scale,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(depth_model: DepthModelConfig) -> ControllerConfig {
+        ControllerConfig {
+            linear_transform: [[1.0, 0.0], [0.0, 1.0]],
+            translation: [0.0, 0.0],
+            depth_image: vec![0.259, 0.249, 0.240, 0.230, 0.220],
+            depth_robot: vec![-32.0, -32.0, -25.0, -14.0, -8.0],
+            target_labels: vec![],
+            depth_model,
+            z_limits: None,
+            max_extrapolation: 0.01,
+            stability: Default::default(),
+            gripper: Default::default(),
+        }
+    }
+
+    #[test]
+    fn piecewise_linear_model() -> Fallible<()> {
+        let model = DepthModel::fit(&config(DepthModelConfig::PiecewiseLinear))?;
+
+        let RobotZ { z, in_range, .. } = model.evaluate(0.235);
+        assert!((z - (-19.5)).abs() < 1e-3);
+        assert!(in_range);
+
+        // extrapolated and clamped
+        let RobotZ {
+            z,
+            in_range,
+            clamped,
+        } = model.evaluate(0.215);
+        assert!(in_range && clamped);
+        assert_eq!(z, -8.0);
+
+        let RobotZ { in_range, .. } = model.evaluate(0.1);
+        assert!(!in_range);
+        Ok(())
+    }
+
+    #[test]
+    fn polynomial_model() -> Fallible<()> {
+        let model = DepthModel::fit(&config(DepthModelConfig::Polynomial { degree: 2 }))?;
+        let RobotZ { z, .. } = model.evaluate(0.235);
+        assert!(z > -25.0 && z < -14.0);
+
+        assert!(DepthModel::fit(&config(DepthModelConfig::Polynomial { degree: 5 })).is_err());
+        Ok(())
+    }
+}
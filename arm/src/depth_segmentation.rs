@@ -0,0 +1,229 @@
+use crate::{
+    config::DepthSegmentationConfig,
+    frame_source::{DepthImage, Intrinsics},
+};
+use failure::Fallible;
+use image::{GrayImage, Luma};
+use nalgebra::{Matrix3, Point3, Vector3};
+
+/// A plane in camera coordinates, whose normal points to the camera side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub offset: f32,
+}
+
+impl Plane {
+    fn from_points(a: &Point3<f32>, b: &Point3<f32>, c: &Point3<f32>) -> Option<Self> {
+        let normal = (b - a).cross(&(c - a));
+        let norm = normal.norm();
+        if norm <= 1e-9 {
+            return None;
+        }
+        Some(Self::new(normal / norm, a))
+    }
+
+    // Creates the plane through the point, flipping the normal toward the camera.
+    fn new(normal: Vector3<f32>, point: &Point3<f32>) -> Self {
+        let offset = -normal.dot(&point.coords);
+        if offset < 0.0 {
+            Self {
+                normal: -normal,
+                offset: -offset,
+            }
+        } else {
+            Self { normal, offset }
+        }
+    }
+
+    /// The signed distance from the plane, positive on the camera side.
+    pub fn height(&self, point: &Point3<f32>) -> f32 {
+        self.normal.dot(&point.coords) + self.offset
+    }
+}
+
+/// Fits the dominant plane among points by RANSAC and refines it by least squares.
+pub fn fit_plane(points: &[Point3<f32>], distance: f32, n_iterations: usize) -> Option<Plane> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    // a fixed seed keeps results reproducible on recorded frames
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    let mut best: Option<(Plane, usize)> = None;
+
+    for _ in 0..n_iterations {
+        let a = &points[rng.next_index(points.len())];
+        let b = &points[rng.next_index(points.len())];
+        let c = &points[rng.next_index(points.len())];
+        let plane = match Plane::from_points(a, b, c) {
+            Some(plane) => plane,
+            None => continue,
+        };
+
+        let n_inliers = points
+            .iter()
+            .filter(|point| plane.height(point).abs() <= distance)
+            .count();
+        if best.map(|(_, n_best)| n_inliers > n_best).unwrap_or(true) {
+            best = Some((plane, n_inliers));
+        }
+    }
+
+    let (plane, _) = best?;
+    let inliers = points
+        .iter()
+        .filter(|point| plane.height(point).abs() <= distance)
+        .collect::<Vec<_>>();
+    Some(refine_plane(&inliers).unwrap_or(plane))
+}
+
+// Fits the plane minimizing squared distances, whose normal is the
+// eigenvector of the smallest eigenvalue of the covariance.
+fn refine_plane(points: &[&Point3<f32>]) -> Option<Plane> {
+    if points.len() < 3 {
+        return None;
+    }
+
+    let centroid = points
+        .iter()
+        .fold(Vector3::zeros(), |sum, point| sum + point.coords)
+        / points.len() as f32;
+    let covariance = points.iter().fold(Matrix3::zeros(), |sum, point| {
+        let diff = point.coords - centroid;
+        sum + diff * diff.transpose()
+    });
+
+    let eigen = covariance.symmetric_eigen();
+    let (index, _) = eigen
+        .eigenvalues
+        .iter()
+        .enumerate()
+        .min_by(|(_, lhs), (_, rhs)| lhs.partial_cmp(rhs).unwrap())?;
+    let normal: Vector3<f32> = eigen.eigenvectors.column(index).into_owned();
+
+    Some(Plane::new(normal, &Point3::from(centroid)))
+}
+
+/// Fits the table plane from the depth image and marks pixels standing
+/// within the configured height range above it.
+///
+/// It returns `None` if the table plane cannot be found.
+pub fn segment(
+    depth_image: &DepthImage,
+    intrinsics: &Intrinsics,
+    config: &DepthSegmentationConfig,
+) -> Fallible<Option<GrayImage>> {
+    let DepthSegmentationConfig {
+        min_height,
+        max_height,
+        plane_distance,
+        n_iterations,
+        sample_stride,
+        ..
+    } = *config;
+    let width = depth_image.width();
+    let height = depth_image.height();
+    let stride = sample_stride.max(1);
+
+    let deproject = |x: usize, y: usize| -> Fallible<Option<Point3<f32>>> {
+        let depth = depth_image.distance(x, y)?;
+        if depth > 0.0 {
+            Ok(Some(intrinsics.deproject(x as f32, y as f32, depth)))
+        } else {
+            Ok(None)
+        }
+    };
+
+    // fit table plane on sampled points
+    let mut samples = vec![];
+    for y in (0..height).step_by(stride) {
+        for x in (0..width).step_by(stride) {
+            if let Some(point) = deproject(x, y)? {
+                samples.push(point);
+            }
+        }
+    }
+    let plane = match fit_plane(&samples, plane_distance, n_iterations) {
+        Some(plane) => plane,
+        None => return Ok(None),
+    };
+
+    // mark pixels above the table
+    let max_height = max_height.unwrap_or(f32::INFINITY);
+    let mut mask = GrayImage::new(width as u32, height as u32);
+    for y in 0..height {
+        for x in 0..width {
+            if let Some(point) = deproject(x, y)? {
+                let point_height = plane.height(&point);
+                if point_height >= min_height && point_height <= max_height {
+                    mask.put_pixel(x as u32, y as u32, Luma([255]));
+                }
+            }
+        }
+    }
+
+    Ok(Some(mask))
+}
+
+/// The xorshift pseudo random number generator.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_index(&mut self, len: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % len as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{BrickConfig, SegmentationMode, SyntheticConfig},
+        synthetic::SyntheticScene,
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn segment_bricks_above_table() -> Fallible<()> {
+        let scene = SyntheticScene::new(SyntheticConfig {
+            width: 320,
+            height: 240,
+            fps: 30.0,
+            fov: 69.4,
+            table_distance: 0.26,
+            table_color: [100, 30, 200],
+            layer_height: 0.0095,
+            bricks: vec![BrickConfig {
+                // the same color as the table
+                color: [100, 30, 200],
+                size: [0.032, 0.016],
+                position: [0.02, -0.01],
+                angle: 30.0,
+                n_layers: 2,
+            }],
+        })?;
+        let msg = scene.render(Duration::from_secs(0));
+        let truth = &msg.ground_truth.as_ref().unwrap()[0];
+
+        let config = DepthSegmentationConfig {
+            mode: SegmentationMode::Replace,
+            label: String::from("depth"),
+            min_height: 0.01,
+            max_height: None,
+            plane_distance: 0.005,
+            n_iterations: 50,
+            sample_stride: 4,
+        };
+        let mask = segment(&msg.depth_image, &msg.intrinsics, &config)?.unwrap();
+
+        let Luma([center]) = *mask.get_pixel(truth.x.round() as u32, truth.y.round() as u32);
+        let Luma([corner]) = *mask.get_pixel(0, 0);
+        assert_eq!(center, 255);
+        assert_eq!(corner, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use crate::message::RealSenseMessage;
+use async_trait::async_trait;
+use failure::Fallible;
+use image::{ImageBuffer, Luma};
+use nalgebra::Point3;
+use serde::{Deserialize, Serialize};
+
+/// The raw Z16 depth image along with its depth scale.
+#[derive(Debug, Clone)]
+pub struct DepthImage {
+    pub image: ImageBuffer<Luma<u16>, Vec<u16>>,
+    /// meters per depth unit
+    pub scale: f32,
+}
+
+impl DepthImage {
+    pub fn width(&self) -> usize {
+        self.image.width() as usize
+    }
+
+    pub fn height(&self) -> usize {
+        self.image.height() as usize
+    }
+
+    /// Gets the distance in meters at pixel (x, y). Returns zero on holes.
+    pub fn distance(&self, x: usize, y: usize) -> Fallible<f32> {
+        let (width, height) = self.image.dimensions();
+        if x >= width as usize || y >= height as usize {
+            failure::bail!(
+                "pixel ({}, {}) is out of bound of {}x{} depth image",
+                x,
+                y,
+                width,
+                height
+            );
+        }
+        let Luma([value]) = *self.image.get_pixel(x as u32, y as u32);
+        Ok(value as f32 * self.scale)
+    }
+}
+
+/// The pinhole camera intrinsics of the color stream, which the depth is aligned to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Intrinsics {
+    pub width: usize,
+    pub height: usize,
+    /// horizontal coordinate of the principal point in pixels
+    pub ppx: f32,
+    /// vertical coordinate of the principal point in pixels
+    pub ppy: f32,
+    /// focal length in multiples of pixel width
+    pub fx: f32,
+    /// focal length in multiples of pixel height
+    pub fy: f32,
+}
+
+impl Intrinsics {
+    /// Deprojects the pixel (x, y) at the depth in meters to a point in camera
+    /// coordinates, where x points right, y points down and z points forward.
+    pub fn deproject(&self, x: f32, y: f32, depth: f32) -> Point3<f32> {
+        Point3::new(
+            (x - self.ppx) / self.fx * depth,
+            (y - self.ppy) / self.fy * depth,
+            depth,
+        )
+    }
+}
+
+/// The source of synchronized color, depth and point cloud frames.
+///
+/// The RealSense provider polls a frame source and broadcasts the frames
+/// to downstream workers. It does not care whether the frames come from
+/// a live device or elsewhere.
+#[async_trait]
+pub trait FrameSource: Send {
+    /// Waits for the next frame. It returns `None` when the source is exhausted.
+    async fn next_frame(&mut self) -> Fallible<Option<RealSenseMessage>>;
+}
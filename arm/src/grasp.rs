@@ -0,0 +1,87 @@
+use nalgebra::Point3;
+
+/// Computes the narrowest width in meters of the polygon in camera x-y plane,
+/// which is the opening the gripper needs to close on the object.
+pub fn grasp_width(polygon3d: &[Point3<f32>]) -> Option<f32> {
+    if polygon3d.len() < 3 {
+        return None;
+    }
+
+    // project onto directions in steps of one degree
+    (0..180)
+        .map(|degree| {
+            let (sin, cos) = (degree as f32).to_radians().sin_cos();
+            let (min, max) =
+                polygon3d
+                    .iter()
+                    .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), point| {
+                        let projection = point.x * cos + point.y * sin;
+                        (min.min(projection), max.max(projection))
+                    });
+            max - min
+        })
+        .fold(None, |narrowest: Option<f32>, width| match narrowest {
+            Some(narrowest) if narrowest <= width => Some(narrowest),
+            _ => Some(width),
+        })
+}
+
+/// Picks the end effector rotation in degrees to grasp the object, which
+/// looks the same every half turn, so `r` can be shifted by multiples of 180
+/// degrees.
+///
+/// The Dobot rotation `r` is the sum of the base angle and the joint-4 angle.
+/// It returns the rotation closest to `current_r` whose joint-4 angle lies
+/// within the limits, or `None` if there is no such rotation.
+pub fn normalize_rotation(
+    r: f32,
+    base_angle: f32,
+    current_r: f32,
+    joint4_limits: [f32; 2],
+) -> Option<f32> {
+    let [min_joint4, max_joint4] = joint4_limits;
+    let offset = ((current_r - r) / 180.0).round();
+    (-2..=2)
+        .map(|turns| r + (offset + turns as f32) * 180.0)
+        .filter(|r| {
+            let joint4 = r - base_angle;
+            joint4 >= min_joint4 && joint4 <= max_joint4
+        })
+        .fold(None, |closest: Option<f32>, r| match closest {
+            Some(closest) if (closest - current_r).abs() <= (r - current_r).abs() => Some(closest),
+            _ => Some(r),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate_within_joint_limits() {
+        // take the short way round rather than turning 170 degrees
+        let r = normalize_rotation(179.0, 0.0, 9.0, [-90.0, 90.0]).unwrap();
+        assert!((r - -1.0).abs() < 1e-4);
+
+        // the base turns 60 degrees, which leaves joint 4 less room
+        let r = normalize_rotation(-80.0, 60.0, 9.0, [-90.0, 90.0]).unwrap();
+        assert!((r - 100.0).abs() < 1e-4);
+
+        // narrow limits may not be reachable by any half turn
+        assert!(normalize_rotation(60.0, 0.0, 0.0, [-20.0, 20.0]).is_none());
+    }
+
+    #[test]
+    fn measure_narrowest_width() {
+        // a 0.04 x 0.02 rectangle rotated by 30 degrees
+        let (sin, cos) = 30f32.to_radians().sin_cos();
+        let polygon = [(0.02, 0.01), (-0.02, 0.01), (-0.02, -0.01), (0.02, -0.01)]
+            .iter()
+            .map(|&(x, y)| Point3::new(x * cos - y * sin, x * sin + y * cos, 0.3))
+            .collect::<Vec<_>>();
+        let width = grasp_width(&polygon).unwrap();
+        assert!((width - 0.02).abs() < 1e-4);
+
+        assert!(grasp_width(&polygon[..2]).is_none());
+    }
+}
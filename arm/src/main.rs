@@ -1,21 +1,34 @@
 mod config;
 mod controller;
+mod dataset;
+mod depth_filter;
+mod depth_model;
+mod depth_segmentation;
+mod frame_source;
+mod grasp;
 mod message;
+mod object_depth;
 mod object_detector;
+mod playback;
 mod processor;
 mod realsense_provider;
+mod recorder;
+mod stability;
 mod state;
+mod synthetic;
+mod tracker;
 mod utils;
+mod validation;
 mod visualizer;
 
 use crate::{
     config::Config, controller::Controller, object_detector::ObjectDetector,
-    realsense_provider::RealSenseProvider, state::GlobalState, utils::WatchedObject,
-    visualizer::Visualizer,
+    realsense_provider::RealSenseProvider, recorder::Recorder, state::GlobalState,
+    utils::WatchedObject, visualizer::Visualizer,
 };
 use argh::FromArgs;
 use failure::Fallible;
-use log::info;
+use log::{info, warn};
 use std::{path::PathBuf, sync::Arc};
 
 #[derive(FromArgs, Debug, Clone)]
@@ -24,28 +37,72 @@ struct Args {
     #[argh(option, default = "PathBuf::from(\"config.json\")")]
     /// configuration file path.
     pub config: PathBuf,
+    #[argh(option)]
+    /// overlay configuration file merged on top of the configuration, can be given multiple times.
+    pub overlay: Vec<PathBuf>,
+    #[argh(option)]
+    /// override a configuration field, e.g. --set controller.translation=[373,167].
+    pub set: Vec<String>,
+    #[argh(switch)]
+    /// validate the configuration and exit without opening devices.
+    pub check_config: bool,
 }
 
 #[tokio::main]
 async fn main() -> Fallible<()> {
     pretty_env_logger::init();
 
-    // init global state
-    let state = WatchedObject::new(GlobalState {
-        is_dobot_busy: false,
-        enable_auto_grab: false,
-        termiate: false,
-        facing: true,
-    });
-
     // parse arguments
     let args: Args = argh::from_env();
     let Args {
         config: config_path,
+        overlay,
+        set,
+        check_config,
     } = args;
 
     // load config file
-    let config = Arc::new(Config::open(config_path)?);
+    let config = {
+        let value = config::merge_layers(&config_path, &overlay, &set)?;
+        info!(
+            "merged configuration:\n{}",
+            serde_json::to_string_pretty(&value)?
+        );
+        let config = Config::from_value(value)?;
+        info!(
+            "object detector parameters in use:\n{}",
+            serde_json::to_string_pretty(&config.object_detector)?
+        );
+        Arc::new(config)
+    };
+
+    // validate config
+    {
+        let issues = config.validate();
+        let n_errors = issues.iter().filter(|issue| issue.is_error()).count();
+        if check_config {
+            for issue in issues.iter() {
+                println!("{}", issue);
+            }
+            if n_errors > 0 {
+                failure::bail!("found {} errors in {}", n_errors, config_path.display());
+            }
+            println!("{} is valid", config_path.display());
+            return Ok(());
+        }
+        for issue in issues.iter() {
+            warn!("{}", issue);
+        }
+    }
+
+    // init global state
+    let state = WatchedObject::new(GlobalState {
+        is_dobot_busy: false,
+        enable_auto_grab: false,
+        termiate: false,
+        facing: true,
+        is_recording: config.recorder.enabled,
+    });
 
     // start visaulizer
     let visualizer_handle = Visualizer::start(config.clone(), state.clone());
@@ -54,6 +111,13 @@ async fn main() -> Fallible<()> {
     let realsense_handle =
         RealSenseProvider::start(config.clone(), visualizer_handle.msg_tx.clone());
 
+    // start recorder
+    let recorder_handle = Recorder::start(
+        config.clone(),
+        realsense_handle.recorder_msg_rx,
+        state.clone(),
+    );
+
     // start object detector
     let detector_handle = ObjectDetector::start(
         config.clone(),
@@ -76,12 +140,14 @@ async fn main() -> Fallible<()> {
         let detector_wait = detector_handle.handle;
         let controller_wait = controller_handle.handle;
         let realsense_wait = realsense_handle.handle;
+        let recorder_wait = recorder_handle.handle;
         let visualizer_wait = visualizer_handle.handle;
 
         futures::try_join!(
             async move { Fallible::Ok(detector_wait.await??) },
             async move { Fallible::Ok(controller_wait.await??) },
             async move { Fallible::Ok(realsense_wait.await??) },
+            async move { Fallible::Ok(recorder_wait.await??) },
             async move { Fallible::Ok(visualizer_wait.await??) },
         )?;
     }
@@ -1,6 +1,10 @@
-use crate::object_detector::{Detection, Object};
+use crate::{
+    frame_source::{DepthImage, Intrinsics},
+    object_detector::{Detection, Object},
+    synthetic::BrickPose,
+};
+use image::RgbImage;
 use nalgebra::{Point2, Point3};
-use realsense_rust::frame::{marker as frame_marker, Frame};
 use std::{
     sync::Arc,
     time::{Duration, Instant},
@@ -9,21 +13,21 @@ use std::{
 /// Message type produced by RealSense provider.
 #[derive(Debug)]
 pub struct RealSenseMessage {
-    pub depth_frame: Frame<frame_marker::Depth>,
-    pub color_frame: Frame<frame_marker::Video>,
+    /// time elapsed since the frame source started
+    pub timestamp: Duration,
+    pub color_image: Arc<RgbImage>,
+    pub depth_image: Arc<DepthImage>,
     pub points: Arc<Vec<Point3<f32>>>,
-    pub texture_coordinates: Vec<Point2<f32>>,
+    pub texture_coordinates: Arc<Vec<Point2<f32>>>,
+    pub intrinsics: Intrinsics,
+    /// the ground-truth brick poses, only available for synthetic frames
+    pub ground_truth: Option<Arc<Vec<BrickPose>>>,
 }
 
 /// Message type received by visualizer.
 #[derive(Debug, Clone)]
 pub enum VisualizerMessage {
-    RealSenseData {
-        depth_frame: Frame<frame_marker::Depth>,
-        color_frame: Frame<frame_marker::Video>,
-        points: Arc<Vec<Point3<f32>>>,
-        texture_coordinates: Vec<Point2<f32>>,
-    },
+    RealSenseData(Arc<RealSenseMessage>),
     ObjectDetection(Arc<Detection>),
 }
 
@@ -41,6 +45,7 @@ pub enum ControlMessage {
     Home,
     Reset,
     ToggleAutoGrab,
+    ToggleRecording,
     Switch,
 }
 
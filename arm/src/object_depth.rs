@@ -0,0 +1,155 @@
+use crate::{config::DepthStatistic, frame_source::DepthImage};
+use failure::Fallible;
+use geo::{algorithm::contains::Contains, LineString, Point, Polygon};
+
+/// The depth of an object estimated from the depth pixels within its polygon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthEstimate {
+    /// the depth in meters, or zero if it is not valid
+    pub depth: f32,
+    /// the number of valid depth pixels within the polygon
+    pub n_pixels: usize,
+    /// true if there are enough valid depth pixels
+    pub valid: bool,
+}
+
+/// Estimates the object depth from the nonzero depth pixels within the polygon.
+pub fn estimate_depth(
+    depth_image: &DepthImage,
+    polygon: &LineString<f32>,
+    statistic: DepthStatistic,
+    min_pixels: usize,
+) -> Fallible<DepthEstimate> {
+    let mut depths = polygon_depths(depth_image, polygon)?;
+    let n_pixels = depths.len();
+
+    if n_pixels == 0 || n_pixels < min_pixels {
+        return Ok(DepthEstimate {
+            depth: 0.0,
+            n_pixels,
+            valid: false,
+        });
+    }
+
+    depths.sort_by(|lhs, rhs| lhs.partial_cmp(rhs).unwrap());
+    let depth = match statistic {
+        DepthStatistic::Median => percentile(&depths, 50.0),
+        DepthStatistic::Percentile { percentile: pct } => percentile(&depths, pct),
+        DepthStatistic::TrimmedMean { ratio } => {
+            let n_trimmed = ((n_pixels as f32 * ratio) as usize).min((n_pixels - 1) / 2);
+            let kept = &depths[n_trimmed..(n_pixels - n_trimmed)];
+            kept.iter().sum::<f32>() / kept.len() as f32
+        }
+    };
+
+    Ok(DepthEstimate {
+        depth,
+        n_pixels,
+        valid: true,
+    })
+}
+
+// Collects nonzero depths of pixels whose centers lie within the polygon.
+fn polygon_depths(depth_image: &DepthImage, polygon: &LineString<f32>) -> Fallible<Vec<f32>> {
+    if polygon.0.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let (min_x, min_y, max_x, max_y) = polygon.0.iter().fold(
+        (
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        ),
+        |(min_x, min_y, max_x, max_y), coord| {
+            (
+                min_x.min(coord.x),
+                min_y.min(coord.y),
+                max_x.max(coord.x),
+                max_y.max(coord.y),
+            )
+        },
+    );
+
+    let width = depth_image.width();
+    let height = depth_image.height();
+    let begin_x = min_x.floor().max(0.0) as usize;
+    let begin_y = min_y.floor().max(0.0) as usize;
+    let end_x = (max_x.ceil().max(0.0) as usize + 1).min(width);
+    let end_y = (max_y.ceil().max(0.0) as usize + 1).min(height);
+
+    let region = Polygon::new(polygon.clone(), vec![]);
+    let mut depths = vec![];
+
+    for y in begin_y..end_y {
+        for x in begin_x..end_x {
+            if !region.contains(&Point::new(x as f32, y as f32)) {
+                continue;
+            }
+            let depth = depth_image.distance(x, y)?;
+            if depth > 0.0 {
+                depths.push(depth);
+            }
+        }
+    }
+
+    Ok(depths)
+}
+
+// Interpolates the percentile in [0, 100] of sorted values.
+fn percentile(sorted: &[f32], pct: f32) -> f32 {
+    let rank = (pct.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f32;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    let frac = rank - lower as f32;
+    sorted[lower] * (1.0 - frac) + sorted[upper] * frac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageBuffer, Luma};
+
+    #[test]
+    fn ignore_holes_and_outliers() -> Fallible<()> {
+        // a 10x10 object at 0.25m with holes and a spike in the middle
+        let image = ImageBuffer::from_fn(20, 20, |x, y| {
+            let value = match (x, y) {
+                (5..=14, 5..=14) if x == y => 0,
+                (9, 10) => 900,
+                (5..=14, 5..=14) => 250,
+                _ => 300,
+            };
+            Luma([value])
+        });
+        let depth_image = DepthImage {
+            image,
+            scale: 0.001,
+        };
+        let polygon = LineString::from(vec![
+            (5.0, 5.0),
+            (14.0, 5.0),
+            (14.0, 14.0),
+            (5.0, 14.0),
+            (5.0, 5.0),
+        ]);
+
+        let estimate = estimate_depth(&depth_image, &polygon, DepthStatistic::Median, 10)?;
+        assert!(estimate.valid);
+        assert!((estimate.depth - 0.25).abs() < 1e-6);
+
+        let estimate = estimate_depth(
+            &depth_image,
+            &polygon,
+            DepthStatistic::TrimmedMean { ratio: 0.1 },
+            10,
+        )?;
+        assert!((estimate.depth - 0.25).abs() < 1e-6);
+
+        let estimate = estimate_depth(&depth_image, &polygon, DepthStatistic::Median, 1000)?;
+        assert!(!estimate.valid);
+
+        Ok(())
+    }
+}
@@ -1,40 +1,108 @@
 use crate::{
-    config::{Config, ObjectDetectorConfig},
+    config::{
+        Config, DepthSegmentationConfig, DepthStatistic, ObjectDetectorConfig, SegmentationMode,
+    },
+    depth_segmentation, grasp,
     message::{DetectorMessage, RealSenseMessage, VisualizerMessage},
+    object_depth::{self, DepthEstimate},
+    tracker::{Pose, Tracker},
     utils::{HackyTryFrom, RateMeter},
 };
 use failure::Fallible;
 use geo::LineString;
-use hacky_arm_common::opencv::{core::Vec3b, prelude::*};
+use hacky_arm_common::opencv::{core::Vec3b, imgproc, prelude::*};
 use hacky_detection::Detector;
-use hacky_detection::Obj;
-use log::info;
-use realsense_rust::prelude::*;
-use std::{sync::Arc, time::Instant};
+use hacky_detection::{Annotation, DetectOptions, DetectionResult, Measurements, Obj};
+use log::{info, warn};
+use nalgebra::Point3;
+use std::{
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 use tokio::{sync::broadcast, task::JoinHandle};
 
+const PARAMS_CHECK_PERIOD: Duration = Duration::from_millis(500);
+const DEFAULT_MIN_DEPTH_PIXELS: usize = 1;
+
 #[derive(Debug)]
 pub struct ObjectDetector {
     config: Arc<Config>,
-    detector: Arc<Detector>,
+    params: Arc<DetectionParams>,
+    tracker: Tracker,
+    /// modification time of the parameter file the detector is built from
+    params_modified: Option<SystemTime>,
+    params_checked: Instant,
     msg_tx: broadcast::Sender<Arc<DetectorMessage>>,
     realsense_msg_rx: broadcast::Receiver<Arc<RealSenseMessage>>,
     viz_msg_tx: broadcast::Sender<VisualizerMessage>,
 }
 
+/// The detector along with the parameters used around it.
+#[derive(Debug)]
+struct DetectionParams {
+    detector: Detector,
+    depth_statistic: DepthStatistic,
+    min_depth_pixels: usize,
+    depth_segmentation: Option<DepthSegmentationConfig>,
+}
+
+impl DetectionParams {
+    fn new(params: &ObjectDetectorConfig) -> Self {
+        Self {
+            detector: params.detector.to_detector(),
+            depth_statistic: params.depth_statistic.unwrap_or_default(),
+            min_depth_pixels: params.min_depth_pixels.unwrap_or(DEFAULT_MIN_DEPTH_PIXELS),
+            depth_segmentation: params.depth_segmentation.clone(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Detection {
+    /// the BGR input image without annotations
     pub image: Arc<Vec<Vec<Vec3b>>>,
     pub objects: Vec<Arc<Object>>,
+    /// the draw primitives of detected objects
+    pub annotations: Vec<Annotation>,
+    /// the intermediate images requested by the visualizer, in BGR
+    pub debug_images: Vec<(String, Arc<Vec<Vec<Vec3b>>>)>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Object {
+    /// the label of the color class
+    pub label: String,
     pub x: i32,
     pub y: i32,
     pub angle: f32,
     pub polygon: LineString<f32>,
+    /// the depth estimated from pixels within the polygon
     pub depth: f32,
+    /// false if there are too few valid depth pixels within the polygon
+    pub depth_valid: bool,
+    /// the number of valid depth pixels within the polygon
+    pub n_depth_pixels: usize,
+    /// the center in camera coordinates in meters
+    pub position: Point3<f32>,
+    /// the polygon in camera coordinates, assuming it lies at the center depth
+    pub polygon3d: Vec<Point3<f32>>,
+    /// the shape and color measurements in the image
+    pub measurements: Measurements,
+    /// the label of the closest shape template, `None` if unknown or not classified
+    pub shape: Option<String>,
+    /// the distance to the closest shape template, where lower is closer
+    pub shape_score: Option<f64>,
+    /// the narrowest width in meters, computed from the 3D polygon
+    pub grasp_width: Option<f32>,
+    /// false if the object is wider than the gripper opening
+    pub graspable: bool,
+    /// the ID of the track across frames
+    pub track_id: u64,
+    /// the number of frames the track was observed
+    pub track_age: usize,
+    /// the pose smoothed over the track
+    pub smoothed_pose: Pose,
 }
 
 impl ObjectDetector {
@@ -43,78 +111,25 @@ impl ObjectDetector {
         realsense_msg_rx: broadcast::Receiver<Arc<RealSenseMessage>>,
         viz_msg_tx: broadcast::Sender<VisualizerMessage>,
     ) -> ObjectDetectorHandle {
-        let Config {
-            object_detector:
-                ObjectDetectorConfig {
-                    inversion,
-                    blur_kernel,
-                    n_dilations,
-                    dilation_kernel,
-                    n_erosions,
-                    erosion_kernel,
-                    n_objects,
-                    min_arc_length,
-                    max_arc_length,
-                    roi,
-                    lower_bound,
-                    upper_bound,
-                },
-            ..
-        } = *config;
-
         let (msg_tx, msg_rx) = broadcast::channel(2);
 
         let handle = tokio::spawn(async move {
             // init detector
-            let detector = {
-                let mut detector = Detector::default();
-                if let Some(inversion) = inversion {
-                    detector.inversion = inversion;
-                }
-                if let Some(blur_kernel) = blur_kernel {
-                    detector.blur_kernel = blur_kernel;
-                }
-                if let Some(n_dilations) = n_dilations {
-                    detector.n_dilations = n_dilations;
-                }
-                if let Some(dilation_kernel) = dilation_kernel {
-                    detector.dilation_kernel = dilation_kernel;
-                }
-                if let Some(n_erosions) = n_erosions {
-                    detector.n_erosions = n_erosions;
-                }
-                if let Some(erosion_kernel) = erosion_kernel {
-                    detector.erosion_kernel = erosion_kernel;
-                }
-                if let Some(n_objects) = n_objects {
-                    detector.n_objects = n_objects;
-                }
-                if let Some(min_arc_length) = min_arc_length {
-                    detector.min_arc_length = min_arc_length;
-                }
-                if let Some(max_arc_length) = max_arc_length {
-                    detector.max_arc_length = max_arc_length;
-                }
-                if let Some(roi) = roi {
-                    detector.roi = roi;
-                }
-                if let Some(lower_bound) = lower_bound {
-                    detector.lower_bound = lower_bound;
-                }
-                if let Some(upper_bound) = upper_bound {
-                    detector.upper_bound = upper_bound;
-                }
-
-                // turn off position drawing, move it to visualizer
-                detector.draw_position = false;
-
-                Arc::new(detector)
-            };
+            let params = Arc::new(DetectionParams::new(&config.object_detector));
+            let tracker = Tracker::new(config.tracker);
+            let params_modified = config
+                .object_detector
+                .params_file
+                .as_ref()
+                .and_then(|path| params_modified_time(path).ok());
 
             // start worker
             let provider = Self {
                 config,
-                detector,
+                params,
+                tracker,
+                params_modified,
+                params_checked: Instant::now(),
                 msg_tx,
                 realsense_msg_rx,
                 viz_msg_tx,
@@ -137,34 +152,88 @@ impl ObjectDetector {
                 Err(broadcast::RecvError::Lagged(_)) => continue,
                 Err(broadcast::RecvError::Closed) => break,
             };
-            let detector = self.detector.clone();
+            self.reload_detector();
+            let params = self.params.clone();
+            let config = self.config.clone();
 
             // run detection
             // the _blocking_ call is necessary since the detection may take long time
             let detection = tokio::task::spawn(async move {
                 let RealSenseMessage {
-                    color_frame,
-                    depth_frame,
+                    color_image,
+                    depth_image,
+                    intrinsics,
                     ..
                 } = &*input_msg;
 
                 // detect objects
-                let color_image = color_frame.image()?;
-                let mut color_mat: Mat = HackyTryFrom::try_from(&color_image)?;
+                let color_mat: Mat = HackyTryFrom::try_from(&**color_image)?;
+                let debug_names = &config.visualizer.debug_images;
+                let options = DetectOptions {
+                    annotations: true,
+                    debug_images: !debug_names.is_empty(),
+                };
+
+                let DetectionParams {
+                    detector,
+                    depth_statistic,
+                    min_depth_pixels,
+                    depth_segmentation,
+                } = &*params;
+
+                // the color masks and measurements are computed on the normalized image
+                let normalized = detector.normalize(&color_mat)?;
 
-                let objects2d = detector.detect(&mut color_mat)?;
+                let masks = match depth_segmentation {
+                    Some(seg_config) => {
+                        let mut masks = match seg_config.mode {
+                            SegmentationMode::Replace => vec![],
+                            SegmentationMode::Combine => detector.color_masks(&normalized)?,
+                        };
+                        match depth_segmentation::segment(depth_image, intrinsics, seg_config)? {
+                            Some(mask) => {
+                                let mask: Mat = HackyTryFrom::try_from(&mask)?;
+                                masks.push((seg_config.label.clone(), mask));
+                            }
+                            None => warn!("unable to find the table plane"),
+                        }
+                        masks
+                    }
+                    None => detector.color_masks(&normalized)?,
+                };
+                let DetectionResult {
+                    objects: objects2d,
+                    annotations,
+                    mut debug_images,
+                } = detector.detect_masks(&normalized, &masks, &options)?;
+                if options.debug_images && !detector.normalization.is_empty() {
+                    debug_images.push(("normalized".to_owned(), normalized));
+                }
 
                 // get distance of each object
                 let objects = objects2d
                     .into_iter()
                     .map(|obj| {
                         let Obj {
+                            label,
                             x,
                             y,
                             angle,
                             polygon,
+                            measurements,
+                            shape,
+                            shape_score,
                         } = obj;
-                        let distance = depth_frame.distance(x as usize, y as usize)?;
+                        let DepthEstimate {
+                            depth: distance,
+                            n_pixels,
+                            valid,
+                        } = object_depth::estimate_depth(
+                            depth_image,
+                            &polygon,
+                            *depth_statistic,
+                            *min_depth_pixels,
+                        )?;
                         // imgproc::put_text(
                         //     &mut color_mat,
                         //     &format!("depth: {:.2}(m)", distance),
@@ -176,26 +245,79 @@ impl ObjectDetector {
                         //     imgproc::LINE_8,
                         //     false,
                         // )?;
+                        let position = intrinsics.deproject(x as f32, y as f32, distance);
+                        let polygon3d = polygon
+                            .points_iter()
+                            .map(|point| intrinsics.deproject(point.x(), point.y(), distance))
+                            .collect::<Vec<_>>();
+                        let grasp_width = grasp::grasp_width(&polygon3d);
+                        let graspable = match (config.controller.gripper.max_opening, grasp_width) {
+                            (Some(max_opening), Some(width)) => width <= max_opening,
+                            _ => true,
+                        };
+                        let smoothed_pose = Pose {
+                            x: x as f32,
+                            y: y as f32,
+                            angle,
+                            depth: distance,
+                        };
                         let object = Object {
+                            label,
                             x,
                             y,
                             angle,
                             polygon,
                             depth: distance,
+                            depth_valid: valid,
+                            n_depth_pixels: n_pixels,
+                            position,
+                            polygon3d,
+                            measurements,
+                            shape,
+                            shape_score,
+                            grasp_width,
+                            graspable,
+                            track_id: 0,
+                            track_age: 0,
+                            smoothed_pose,
                         };
-                        Ok(Arc::new(object))
+                        Ok(object)
                     })
                     .collect::<Fallible<Vec<_>>>()?;
 
                 let image = Arc::new(color_mat.to_vec_2d::<Vec3b>()?);
+                let debug_images = debug_images
+                    .into_iter()
+                    .filter(|(name, _)| debug_names.contains(name))
+                    .map(|(name, mat)| {
+                        let mat = if mat.channels()? == 1 {
+                            let mut bgr = Mat::default()?;
+                            imgproc::cvt_color(&mat, &mut bgr, imgproc::COLOR_GRAY2BGR, 0)?;
+                            bgr
+                        } else {
+                            mat
+                        };
+                        Ok((name, Arc::new(mat.to_vec_2d::<Vec3b>()?)))
+                    })
+                    .collect::<Fallible<Vec<_>>>()?;
 
-                let detection = Detection { image, objects };
-
-                // compute objects and points correspondences
-                Fallible::Ok(Arc::new(detection))
+                Fallible::Ok((image, objects, annotations, debug_images))
             })
             .await??;
 
+            // associate objects with tracks
+            let detection = {
+                let (image, mut objects, annotations, debug_images) = detection;
+                self.tracker.update(&mut objects);
+                let objects = objects.into_iter().map(Arc::new).collect();
+                Arc::new(Detection {
+                    image,
+                    objects,
+                    annotations,
+                    debug_images,
+                })
+            };
+
             // send to visualizer
             {
                 let msg = VisualizerMessage::ObjectDetection(Arc::clone(&detection));
@@ -219,6 +341,107 @@ impl ObjectDetector {
         info!("object detector finished");
         Ok(())
     }
+
+    /// Rebuilds the detector if the parameter file was modified.
+    /// It keeps the current detector if the new parameters are invalid.
+    fn reload_detector(&mut self) {
+        let path = match &self.config.object_detector.params_file {
+            Some(path) => path,
+            None => return,
+        };
+
+        if self.params_checked.elapsed() < PARAMS_CHECK_PERIOD {
+            return;
+        }
+        self.params_checked = Instant::now();
+
+        let modified = match params_modified_time(path) {
+            Ok(modified) => modified,
+            Err(err) => {
+                warn!("unable to check {}: {:?}", path.display(), err);
+                return;
+            }
+        };
+        if self.params_modified == Some(modified) {
+            return;
+        }
+        self.params_modified = Some(modified);
+
+        let params = match ObjectDetectorConfig::open(path, &self.config.object_detector.overrides)
+        {
+            Ok(params) => params,
+            Err(err) => {
+                warn!(
+                    "failed to reload {}, keep previous parameters: {:?}",
+                    path.display(),
+                    err
+                );
+                return;
+            }
+        };
+
+        let issues = params.validate();
+        for issue in issues.iter() {
+            warn!("{}", issue);
+        }
+        let n_errors = issues.iter().filter(|issue| issue.is_error()).count();
+        if n_errors > 0 {
+            warn!(
+                "{} has {} errors, keep previous parameters",
+                path.display(),
+                n_errors
+            );
+            return;
+        }
+
+        self.params = Arc::new(DetectionParams::new(&params));
+        info!(
+            "reloaded object detector parameters from {}",
+            path.display()
+        );
+    }
+}
+
+fn params_modified_time(path: &Path) -> std::io::Result<SystemTime> {
+    std::fs::metadata(path)?.modified()
+}
+
+/// Builds a graspable brick of 20x10 pixels centered at the pixel, for tests.
+#[cfg(test)]
+pub fn object_at(x: i32, y: i32, angle: f32) -> Object {
+    let (xf, yf) = (x as f32, y as f32);
+    let polygon = LineString::from(vec![
+        (xf - 10.0, yf - 5.0),
+        (xf + 10.0, yf - 5.0),
+        (xf + 10.0, yf + 5.0),
+        (xf - 10.0, yf + 5.0),
+        (xf - 10.0, yf - 5.0),
+    ]);
+    Object {
+        label: String::from("brick"),
+        x,
+        y,
+        angle,
+        polygon,
+        depth: 0.25,
+        depth_valid: true,
+        n_depth_pixels: 200,
+        position: Point3::origin(),
+        polygon3d: vec![],
+        measurements: Measurements::default(),
+        shape: None,
+        shape_score: None,
+        grasp_width: None,
+        graspable: true,
+        track_id: 0,
+        track_age: 0,
+        smoothed_pose: Pose {
+            x: 0.0,
+            y: 0.0,
+            angle: 0.0,
+            depth: 0.0,
+        },
+    }
 }
 
 pub struct ObjectDetectorHandle {
@@ -0,0 +1,81 @@
+use crate::{
+    config::PlaybackConfig, dataset, frame_source::FrameSource, message::RealSenseMessage,
+};
+use async_trait::async_trait;
+use failure::Fallible;
+use log::info;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// The frame source that replays a recorded session directory.
+#[derive(Debug)]
+pub struct PlaybackSource {
+    frames: Vec<PathBuf>,
+    cursor: usize,
+    speed: f64,
+    repeat: bool,
+    /// wall clock time and recorded timestamp of the first replayed frame
+    origin: Option<(Instant, Duration)>,
+}
+
+impl PlaybackSource {
+    pub fn open(config: &PlaybackConfig) -> Fallible<Self> {
+        let PlaybackConfig { dir, speed, repeat } = config;
+
+        if !speed.is_finite() || *speed <= 0.0 {
+            failure::bail!("playback speed must be positive, but get {}", speed);
+        }
+
+        let frames = dataset::list_frames(dir)?;
+        if frames.is_empty() {
+            failure::bail!("no frames found in {}", dir.display());
+        }
+        info!("replaying {} frames from {}", frames.len(), dir.display());
+
+        Ok(Self {
+            frames,
+            cursor: 0,
+            speed: *speed,
+            repeat: *repeat,
+            origin: None,
+        })
+    }
+}
+
+#[async_trait]
+impl FrameSource for PlaybackSource {
+    async fn next_frame(&mut self) -> Fallible<Option<RealSenseMessage>> {
+        if self.cursor >= self.frames.len() {
+            if !self.repeat {
+                info!("playback finished");
+                return Ok(None);
+            }
+            self.cursor = 0;
+            self.origin = None;
+        }
+
+        let path = self.frames[self.cursor].clone();
+        self.cursor += 1;
+        let msg = tokio::task::spawn_blocking(move || dataset::read_frame(path)).await??;
+
+        // wait until the frame is due
+        match self.origin {
+            Some((start_time, start_timestamp)) => {
+                let offset = msg
+                    .timestamp
+                    .checked_sub(start_timestamp)
+                    .unwrap_or_default()
+                    .div_f64(self.speed);
+                let deadline = tokio::time::Instant::from_std(start_time + offset);
+                tokio::time::delay_until(deadline).await;
+            }
+            None => {
+                self.origin = Some((Instant::now(), msg.timestamp));
+            }
+        }
+
+        Ok(Some(msg))
+    }
+}
@@ -1,16 +1,23 @@
 use crate::{
-    config::{Config, RealSenseConfig},
+    config::{Config, DepthCameraConfig, FrameSourceConfig, RealSenseConfig, VideoCameraConfig},
+    depth_filter::DepthFilterChain,
+    frame_source::{DepthImage, FrameSource, Intrinsics},
     message::{RealSenseMessage, VisualizerMessage},
+    playback::PlaybackSource,
+    synthetic::SyntheticSource,
     utils::RateMeter,
 };
+use async_trait::async_trait;
 use failure::Fallible;
+use image::{DynamicImage, ImageBuffer, Luma};
 use log::info;
 use nalgebra::{Point2, Point3};
 use realsense_rust::{
-    frame::marker as frame_marker, processing_block::marker as processing_block_marker,
-    Config as RsConfig, Pipeline, ProcessingBlock, StreamKind,
+    frame::marker as frame_marker, pipeline::marker as pipeline_marker,
+    processing_block::marker as processing_block_marker, sensor::marker as sensor_marker,
+    Config as RsConfig, Frame, Pipeline, ProcessingBlock, Rs2Image, StreamKind,
 };
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 use tokio::{sync::broadcast, task::JoinHandle};
 
 /// The type instantiates the RealSense provider.
@@ -18,6 +25,7 @@ use tokio::{sync::broadcast, task::JoinHandle};
 pub struct RealSenseProvider {
     config: Arc<Config>,
     msg_tx: broadcast::Sender<Arc<RealSenseMessage>>,
+    raw_msg_tx: broadcast::Sender<Arc<RealSenseMessage>>,
     viz_msg_tx: broadcast::Sender<VisualizerMessage>,
 }
 
@@ -28,37 +36,112 @@ impl RealSenseProvider {
         viz_msg_tx: broadcast::Sender<VisualizerMessage>,
     ) -> RealSenseHandle {
         let (msg_tx, msg_rx) = broadcast::channel(2);
+        // the recorder saves unfiltered frames so that they can be replayed with other filters
+        let (raw_msg_tx, recorder_msg_rx) = broadcast::channel(2);
 
         let handle = tokio::spawn(async {
             let provider = Self {
                 config,
                 msg_tx,
+                raw_msg_tx,
                 viz_msg_tx,
             };
             provider.run().await?;
             Ok(())
         });
 
-        RealSenseHandle { msg_rx, handle }
+        RealSenseHandle {
+            msg_rx,
+            recorder_msg_rx,
+            handle,
+        }
     }
 
     async fn run(self) -> Fallible<()> {
         let Config {
             realsense:
                 RealSenseConfig {
+                    source,
+                    depth_filters,
                     depth_camera,
                     video_camera,
                 },
             ..
         } = &*self.config;
 
+        let mut source: Box<dyn FrameSource> = match source {
+            FrameSourceConfig::Device => {
+                Box::new(DeviceSource::open(depth_camera, video_camera).await?)
+            }
+            FrameSourceConfig::Playback(playback) => Box::new(PlaybackSource::open(playback)?),
+            FrameSourceConfig::Synthetic(synthetic) => Box::new(SyntheticSource::new(synthetic)?),
+        };
+        let mut filters = DepthFilterChain::new(depth_filters);
+        let mut rate_meter = RateMeter::seconds();
+
+        loop {
+            let raw_msg = match source.next_frame().await? {
+                Some(msg) => Arc::new(msg),
+                None => break,
+            };
+            let _ = self.raw_msg_tx.send(Arc::clone(&raw_msg));
+
+            // apply depth filters, which keep the temporal state across frames
+            let msg = if filters.is_empty() {
+                raw_msg
+            } else {
+                let (msg, chain) = tokio::task::spawn_blocking(move || {
+                    let msg = filters.process(&raw_msg);
+                    (msg, filters)
+                })
+                .await?;
+                filters = chain;
+                Arc::new(msg)
+            };
+
+            // send to visualizer
+            {
+                let msg = VisualizerMessage::RealSenseData(Arc::clone(&msg));
+                if let Err(_) = self.viz_msg_tx.send(msg) {
+                    break;
+                }
+            }
+
+            // broadcast message
+            let _ = self.msg_tx.send(msg);
+
+            if let Some(rate) = rate_meter.tick(1) {
+                info!("message rate {} fps", rate);
+            }
+        }
+
+        info!("realsense provider finished");
+        Ok(())
+    }
+}
+
+/// The frame source that reads from a live RealSense device.
+pub struct DeviceSource {
+    pipeline: Pipeline<pipeline_marker::Active>,
+    pointcloud: ProcessingBlock<processing_block_marker::PointCloud>,
+    aligner: ProcessingBlock<processing_block_marker::Align>,
+    /// the meters per raw depth value
+    depth_scale: f32,
+    intrinsics: Option<Intrinsics>,
+    start_time: Instant,
+}
+
+impl DeviceSource {
+    pub async fn open(
+        depth_camera: &DepthCameraConfig,
+        video_camera: &VideoCameraConfig,
+    ) -> Fallible<Self> {
         // filters
-        let mut pointcloud = ProcessingBlock::<processing_block_marker::PointCloud>::create()?;
-        let mut aligner =
-            ProcessingBlock::<processing_block_marker::Align>::create(StreamKind::Color)?;
+        let pointcloud = ProcessingBlock::<processing_block_marker::PointCloud>::create()?;
+        let aligner = ProcessingBlock::<processing_block_marker::Align>::create(StreamKind::Color)?;
 
         // setup pipeline
-        let mut pipeline = {
+        let pipeline = {
             let pipeline = Pipeline::new()?;
             let config = RsConfig::new()?
                 .enable_stream(
@@ -79,82 +162,133 @@ impl RealSenseProvider {
                 )?;
             pipeline.start_async(Some(config)).await?
         };
-        let mut rate_meter = RateMeter::seconds();
-
-        loop {
-            // wait for data from device
-            let frames = pipeline.wait_async(None).await?;
-            let frames = aligner
-                .process(frames)?
-                .try_extend_to::<frame_marker::Composite>()?
-                .unwrap();
-
-            let depth_frame = frames.depth_frame()?.unwrap();
-            let color_frame = frames.color_frame()?.unwrap();
-
-            // compute point cloud
-            pointcloud.map_to(color_frame.clone())?;
-            let points_frame = pointcloud.calculate(depth_frame.clone())?;
-            let points = {
-                let pts = points_frame
-                    .vertices()?
-                    .iter()
-                    .map(|vertex| {
-                        let [x, y, z] = vertex.xyz;
-                        Point3::new(x, y, z)
-                    })
-                    .collect::<Vec<_>>();
-                Arc::new(pts)
-            };
-            let texture_coordinates = points_frame
-                .texture_coordinates()?
-                .iter()
-                .map(|vertex| {
-                    let [i, j] = vertex.ij;
-                    assert_eq!(std::mem::size_of::<f32>(), std::mem::size_of_val(&i));
-                    assert_eq!(std::mem::size_of::<f32>(), std::mem::size_of_val(&j));
-                    let x: f32 = unsafe { std::mem::transmute(i) };
-                    let y: f32 = unsafe { std::mem::transmute(j) };
-                    Point2::new(x, y)
-                })
-                .collect::<Vec<_>>();
 
-            // send to visualizer
-            {
-                let msg = VisualizerMessage::RealSenseData {
-                    depth_frame: depth_frame.clone(),
-                    color_frame: color_frame.clone(),
-                    points: Arc::clone(&points),
-                    texture_coordinates: texture_coordinates.clone(),
-                };
-                if let Err(_) = self.viz_msg_tx.send(msg) {
+        // query the depth units of the depth sensor
+        let depth_scale = {
+            let device = pipeline.profile().device()?;
+            let mut depth_scale = None;
+            for sensor in device.query_sensors()?.try_into_iter()? {
+                if let Ok(sensor) = sensor?.try_extend_to::<sensor_marker::Depth>()? {
+                    depth_scale = Some(sensor.depth_units()?);
                     break;
                 }
             }
+            depth_scale.ok_or_else(|| failure::format_err!("no depth sensor is found"))?
+        };
+        info!("depth units {} meters", depth_scale);
 
-            // broadcast message
-            {
-                let msg = RealSenseMessage {
-                    depth_frame,
-                    color_frame,
-                    points,
-                    texture_coordinates,
-                };
-                let _ = self.msg_tx.send(Arc::new(msg));
-            }
+        Ok(Self {
+            pipeline,
+            pointcloud,
+            aligner,
+            depth_scale,
+            intrinsics: None,
+            start_time: Instant::now(),
+        })
+    }
 
-            if let Some(rate) = rate_meter.tick(1) {
-                info!("message rate {} fps", rate);
-            }
+    // the depth is aligned to color, so both share the color stream intrinsics
+    fn intrinsics(&mut self, color_frame: &Frame<frame_marker::Video>) -> Fallible<Intrinsics> {
+        if let Some(intrinsics) = self.intrinsics {
+            return Ok(intrinsics);
         }
 
-        info!("realsense provider finished");
-        Ok(())
+        let raw = color_frame.stream_profile()?.intrinsics()?;
+        let intrinsics = Intrinsics {
+            width: raw.width as usize,
+            height: raw.height as usize,
+            ppx: raw.ppx,
+            ppy: raw.ppy,
+            fx: raw.fx,
+            fy: raw.fy,
+        };
+        self.intrinsics = Some(intrinsics);
+        Ok(intrinsics)
+    }
+}
+
+#[async_trait]
+impl FrameSource for DeviceSource {
+    async fn next_frame(&mut self) -> Fallible<Option<RealSenseMessage>> {
+        // wait for data from device
+        let frames = self.pipeline.wait_async(None).await?;
+        let timestamp = self.start_time.elapsed();
+        let frames = self
+            .aligner
+            .process(frames)?
+            .try_extend_to::<frame_marker::Composite>()?
+            .unwrap();
+
+        let depth_frame = frames.depth_frame()?.unwrap();
+        let color_frame = frames.color_frame()?.unwrap();
+
+        // compute point cloud
+        self.pointcloud.map_to(color_frame.clone())?;
+        let points_frame = self.pointcloud.calculate(depth_frame.clone())?;
+        let points = points_frame
+            .vertices()?
+            .iter()
+            .map(|vertex| {
+                let [x, y, z] = vertex.xyz;
+                Point3::new(x, y, z)
+            })
+            .collect::<Vec<_>>();
+        let texture_coordinates = points_frame
+            .texture_coordinates()?
+            .iter()
+            .map(|vertex| {
+                let [i, j] = vertex.ij;
+                assert_eq!(std::mem::size_of::<f32>(), std::mem::size_of_val(&i));
+                assert_eq!(std::mem::size_of::<f32>(), std::mem::size_of_val(&j));
+                let x: f32 = unsafe { std::mem::transmute(i) };
+                let y: f32 = unsafe { std::mem::transmute(j) };
+                Point2::new(x, y)
+            })
+            .collect::<Vec<_>>();
+
+        // copy images out of device buffers
+        let color_image = {
+            let image: DynamicImage = color_frame.image()?.into();
+            image.to_rgb8()
+        };
+        let depth_image = match depth_frame.image()? {
+            Rs2Image::Luma16(image) => {
+                let (width, height) = image.dimensions();
+                let samples = image
+                    .pixels()
+                    .map(|pixel| {
+                        let Luma([sample]) = *pixel;
+                        sample
+                    })
+                    .collect::<Vec<_>>();
+                ImageBuffer::from_raw(width, height, samples).unwrap()
+            }
+            _ => failure::bail!("depth frame is expected to be in Z16 format"),
+        };
+
+        let scale = self.depth_scale;
+        let intrinsics = self.intrinsics(&color_frame)?;
+
+        let msg = RealSenseMessage {
+            timestamp,
+            color_image: Arc::new(color_image),
+            depth_image: Arc::new(DepthImage {
+                image: depth_image,
+                scale,
+            }),
+            points: Arc::new(points),
+            texture_coordinates: Arc::new(texture_coordinates),
+            intrinsics,
+            ground_truth: None,
+        };
+
+        Ok(Some(msg))
     }
 }
 
 #[derive(Debug)]
 pub struct RealSenseHandle {
     pub msg_rx: broadcast::Receiver<Arc<RealSenseMessage>>,
+    pub recorder_msg_rx: broadcast::Receiver<Arc<RealSenseMessage>>,
     pub handle: JoinHandle<Fallible<()>>,
 }
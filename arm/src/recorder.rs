@@ -0,0 +1,124 @@
+use crate::{
+    config::{Config, RecorderConfig},
+    dataset,
+    message::RealSenseMessage,
+    state::GlobalState,
+    utils::WatchedObject,
+};
+use failure::Fallible;
+use log::{info, warn};
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{sync::broadcast, task::JoinHandle};
+
+/// The recording session in progress.
+#[derive(Debug)]
+struct Session {
+    dir: PathBuf,
+    n_frames: usize,
+}
+
+/// The worker that saves RealSense frames to disk.
+///
+/// Each time the recording is switched on, it creates a new session directory
+/// under the configured directory. Sessions can be replayed by the playback source.
+pub struct Recorder {
+    config: Arc<Config>,
+    msg_rx: broadcast::Receiver<Arc<RealSenseMessage>>,
+    state: WatchedObject<GlobalState>,
+}
+
+impl Recorder {
+    /// Starts the recorder and returns a handle.
+    pub fn start(
+        config: Arc<Config>,
+        msg_rx: broadcast::Receiver<Arc<RealSenseMessage>>,
+        state: WatchedObject<GlobalState>,
+    ) -> RecorderHandle {
+        let handle = tokio::spawn(async move {
+            let recorder = Self {
+                config,
+                msg_rx,
+                state,
+            };
+            recorder.run().await?;
+            Ok(())
+        });
+
+        RecorderHandle { handle }
+    }
+
+    async fn run(mut self) -> Fallible<()> {
+        let RecorderConfig { dir: root_dir, .. } = &self.config.recorder;
+        let mut session: Option<Session> = None;
+
+        loop {
+            let msg = match self.msg_rx.recv().await {
+                Ok(msg) => msg,
+                Err(broadcast::RecvError::Lagged(n_skipped)) => {
+                    if session.is_some() {
+                        warn!("recorder is too slow, {} frames are dropped", n_skipped);
+                    }
+                    continue;
+                }
+                Err(broadcast::RecvError::Closed) => break,
+            };
+
+            if !self.state.read().await.is_recording {
+                if let Some(Session { dir, n_frames }) = session.take() {
+                    info!("saved {} frames to {}", n_frames, dir.display());
+                }
+                continue;
+            }
+
+            if session.is_none() {
+                let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+                let dir = create_session_dir(root_dir, millis)?;
+                info!("start recording to {}", dir.display());
+                session = Some(Session { dir, n_frames: 0 });
+            }
+            let current = session.as_mut().unwrap();
+
+            let dir = current.dir.clone();
+            let index = current.n_frames;
+            tokio::task::spawn_blocking(move || dataset::write_frame(dir, index, &msg)).await??;
+            current.n_frames += 1;
+        }
+
+        if let Some(Session { dir, n_frames }) = session {
+            info!("saved {} frames to {}", n_frames, dir.display());
+        }
+
+        info!("recorder finished");
+        Ok(())
+    }
+}
+
+// Creates a new session directory named by the time. A counter suffix is
+// appended if the name is taken, so a session never overwrites another.
+fn create_session_dir(root_dir: &Path, millis: u128) -> Fallible<PathBuf> {
+    std::fs::create_dir_all(root_dir)?;
+    let mut suffix = 0;
+    loop {
+        let name = if suffix == 0 {
+            format!("session-{}", millis)
+        } else {
+            format!("session-{}-{}", millis, suffix)
+        };
+        let dir = root_dir.join(name);
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => suffix += 1,
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RecorderHandle {
+    pub handle: JoinHandle<Fallible<()>>,
+}
@@ -0,0 +1,163 @@
+use crate::{
+    config::StabilityConfig,
+    object_detector::Object,
+    tracker::{angle_difference, Pose},
+};
+use std::collections::VecDeque;
+
+/// The gate that passes the target only after its pose stays stable
+/// over consecutive frames.
+///
+/// It follows one track at a time. The window restarts whenever the
+/// followed track disappears or another track is chosen.
+#[derive(Debug)]
+pub struct StabilityGate {
+    config: StabilityConfig,
+    track_id: Option<u64>,
+    poses: VecDeque<Pose>,
+}
+
+impl StabilityGate {
+    pub fn new(config: StabilityConfig) -> Self {
+        Self {
+            config,
+            track_id: None,
+            poses: VecDeque::new(),
+        }
+    }
+
+    /// Forgets the followed track and observed poses.
+    pub fn reset(&mut self) {
+        self.track_id = None;
+        self.poses.clear();
+    }
+
+    /// Feeds the target candidates of a new frame in order of preference.
+    ///
+    /// It returns the followed target with the pose averaged over the window
+    /// once the poses in the window are within the tolerances.
+    pub fn update(&mut self, targets: &[&Object]) -> Option<Object> {
+        let StabilityConfig {
+            n_frames,
+            max_position_deviation,
+            max_angle_deviation,
+            max_depth_deviation,
+        } = self.config;
+
+        // keep following the same track if it is still observed
+        let followed = self
+            .track_id
+            .and_then(|id| targets.iter().find(|obj| obj.track_id == id));
+        let target = match followed.or_else(|| targets.first()) {
+            Some(target) => *target,
+            None => {
+                self.reset();
+                return None;
+            }
+        };
+        if self.track_id != Some(target.track_id) {
+            self.reset();
+            self.track_id = Some(target.track_id);
+        }
+
+        self.poses.push_back(Pose::of(target));
+        while self.poses.len() > n_frames.max(1) {
+            self.poses.pop_front();
+        }
+        if self.poses.len() < n_frames {
+            return None;
+        }
+
+        let mean = average_pose(&self.poses);
+        let is_stable = self.poses.iter().all(|pose| {
+            (pose.x - mean.x).hypot(pose.y - mean.y) <= max_position_deviation
+                && angle_difference(pose.angle, mean.angle).abs() <= max_angle_deviation
+                && (pose.depth - mean.depth).abs() <= max_depth_deviation
+        });
+        if !is_stable {
+            return None;
+        }
+
+        Some(Object {
+            x: mean.x.round() as i32,
+            y: mean.y.round() as i32,
+            angle: mean.angle,
+            depth: mean.depth,
+            smoothed_pose: mean,
+            ..target.clone()
+        })
+    }
+}
+
+// Averages poses, where angles are averaged modulo 180 degrees around the first one.
+fn average_pose(poses: &VecDeque<Pose>) -> Pose {
+    let n_poses = poses.len() as f32;
+    let reference = poses[0].angle;
+    let sum = poses.iter().fold(
+        Pose {
+            x: 0.0,
+            y: 0.0,
+            angle: 0.0,
+            depth: 0.0,
+        },
+        |sum, pose| Pose {
+            x: sum.x + pose.x,
+            y: sum.y + pose.y,
+            angle: sum.angle + angle_difference(pose.angle, reference),
+            depth: sum.depth + pose.depth,
+        },
+    );
+
+    Pose {
+        x: sum.x / n_poses,
+        y: sum.y / n_poses,
+        angle: reference + sum.angle / n_poses,
+        depth: sum.depth / n_poses,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_detector::object_at;
+
+    fn object(track_id: u64, x: i32, angle: f32) -> Object {
+        Object {
+            track_id,
+            track_age: 1,
+            smoothed_pose: Pose {
+                x: x as f32,
+                y: 100.0,
+                angle,
+                depth: 0.25,
+            },
+            ..object_at(x, 100, angle)
+        }
+    }
+
+    #[test]
+    fn pass_after_stable_frames() {
+        let mut gate = StabilityGate::new(StabilityConfig {
+            n_frames: 3,
+            max_position_deviation: 2.0,
+            max_angle_deviation: 5.0,
+            max_depth_deviation: 0.005,
+        });
+
+        // the target is still moving
+        assert!(gate.update(&[&object(1, 100, 88.0)]).is_none());
+        assert!(gate.update(&[&object(1, 110, 88.0)]).is_none());
+        assert!(gate.update(&[&object(1, 111, 89.0)]).is_none());
+
+        // the followed track is preferred, and the angle wraps around
+        let target = gate.update(&[&object(2, 300, 0.0), &object(1, 112, -89.0)]);
+        let target = target.expect("the target should be stable");
+        assert_eq!(target.track_id, 1);
+        assert_eq!(target.x, 111);
+        assert!(angle_difference(target.angle, 89.333).abs() < 1e-2);
+
+        // the followed track disappears
+        assert!(gate.update(&[&object(2, 300, 0.0)]).is_none());
+        assert!(gate.update(&[]).is_none());
+    }
+}
@@ -4,4 +4,5 @@ pub struct GlobalState {
     pub enable_auto_grab: bool,
     pub termiate: bool,
     pub facing: bool,
+    pub is_recording: bool,
 }
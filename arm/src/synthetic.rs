@@ -0,0 +1,457 @@
+use crate::{
+    config::{BrickConfig, SyntheticConfig},
+    frame_source::{DepthImage, FrameSource, Intrinsics},
+    message::RealSenseMessage,
+};
+use async_trait::async_trait;
+use failure::Fallible;
+use image::{ImageBuffer, Luma, Rgb, RgbImage};
+use nalgebra::{Point2, Point3};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+const DEPTH_SCALE: f32 = 0.001;
+
+/// The ground-truth pose of a brick stack rendered in a synthetic frame.
+#[derive(Debug, Clone)]
+pub struct BrickPose {
+    /// center of the top surface in pixels
+    pub x: f32,
+    pub y: f32,
+    /// counter-clockwise rotation of the short side from image x axis in
+    /// (-90, 90] degrees, which is the angle the detector reports
+    pub angle: f32,
+    /// distance from the camera to the top surface in meters
+    pub depth: f32,
+    /// center of the top surface in camera coordinates in meters
+    pub position: Point3<f32>,
+    /// corners of the top surface in pixels
+    pub polygon: Vec<Point2<f32>>,
+}
+
+/// The renderer of synthetic scenes of brick stacks on a flat table.
+#[derive(Debug, Clone)]
+pub struct SyntheticScene {
+    config: SyntheticConfig,
+    intrinsics: Intrinsics,
+}
+
+impl SyntheticScene {
+    pub fn new(config: SyntheticConfig) -> Fallible<Self> {
+        let SyntheticConfig {
+            width,
+            height,
+            fov,
+            table_distance,
+            layer_height,
+            ref bricks,
+            ..
+        } = config;
+
+        if width == 0 || height == 0 {
+            failure::bail!("invalid synthetic frame size {}x{}", width, height);
+        }
+        if fov <= 0.0 || fov >= 180.0 {
+            failure::bail!("field of view must be in range (0, 180), but get {}", fov);
+        }
+        for (index, brick) in bricks.iter().enumerate() {
+            let top = table_distance - layer_height * brick.n_layers as f32;
+            if top <= 0.0 {
+                failure::bail!("brick {} is taller than the camera height", index);
+            }
+        }
+
+        let focal = width as f32 / 2.0 / (fov.to_radians() / 2.0).tan();
+        let intrinsics = Intrinsics {
+            width,
+            height,
+            ppx: width as f32 / 2.0,
+            ppy: height as f32 / 2.0,
+            fx: focal,
+            fy: focal,
+        };
+
+        Ok(Self { config, intrinsics })
+    }
+
+    pub fn intrinsics(&self) -> Intrinsics {
+        self.intrinsics
+    }
+
+    /// Computes the poses of bricks as seen by the camera.
+    pub fn ground_truth(&self) -> Vec<BrickPose> {
+        self.config
+            .bricks
+            .iter()
+            .map(|brick| {
+                let BrickConfig {
+                    position: [cx, cy],
+                    angle,
+                    ..
+                } = *brick;
+                let depth = self.top_distance(brick);
+                let polygon = brick_corners(brick)
+                    .iter()
+                    .map(|&(x, y)| self.project(x, y, depth))
+                    .collect();
+                let center = self.project(cx, cy, depth);
+
+                BrickPose {
+                    x: center.x,
+                    y: center.y,
+                    angle: detector_angle(angle),
+                    depth,
+                    position: Point3::new(cx, cy, depth),
+                    polygon,
+                }
+            })
+            .collect()
+    }
+
+    /// Renders the color, depth and point cloud of the scene.
+    pub fn render(&self, timestamp: Duration) -> RealSenseMessage {
+        let SyntheticConfig {
+            width,
+            height,
+            table_distance,
+            table_color,
+            ref bricks,
+            ..
+        } = self.config;
+        let Intrinsics {
+            ppx, ppy, fx, fy, ..
+        } = self.intrinsics;
+
+        let table_rgb = hsv_to_rgb(table_color);
+        let brick_rgbs = bricks
+            .iter()
+            .map(|brick| hsv_to_rgb(brick.color))
+            .collect::<Vec<_>>();
+
+        let mut color_image: RgbImage = ImageBuffer::new(width as u32, height as u32);
+        let mut depth_image = ImageBuffer::new(width as u32, height as u32);
+        let mut points = Vec::with_capacity(width * height);
+        let mut texture_coordinates = Vec::with_capacity(width * height);
+
+        for row in 0..height {
+            for col in 0..width {
+                // cast the ray through the pixel and find the closest hit surface
+                let ray_x = (col as f32 - ppx) / fx;
+                let ray_y = (row as f32 - ppy) / fy;
+
+                let hit = bricks
+                    .iter()
+                    .zip(brick_rgbs.iter())
+                    .filter_map(|(brick, rgb)| {
+                        let distance = self.top_distance(brick);
+                        let inside = brick_contains(brick, ray_x * distance, ray_y * distance);
+                        if inside {
+                            Some((distance, *rgb))
+                        } else {
+                            None
+                        }
+                    })
+                    .min_by(|(lhs, _), (rhs, _)| lhs.partial_cmp(rhs).unwrap());
+                let (distance, rgb) = hit.unwrap_or((table_distance, table_rgb));
+
+                color_image.put_pixel(col as u32, row as u32, Rgb(rgb));
+                depth_image.put_pixel(
+                    col as u32,
+                    row as u32,
+                    Luma([(distance / DEPTH_SCALE).round() as u16]),
+                );
+                points.push(Point3::new(ray_x * distance, ray_y * distance, distance));
+                texture_coordinates.push(Point2::new(
+                    (col as f32 + 0.5) / width as f32,
+                    (row as f32 + 0.5) / height as f32,
+                ));
+            }
+        }
+
+        RealSenseMessage {
+            timestamp,
+            color_image: Arc::new(color_image),
+            depth_image: Arc::new(DepthImage {
+                image: depth_image,
+                scale: DEPTH_SCALE,
+            }),
+            points: Arc::new(points),
+            texture_coordinates: Arc::new(texture_coordinates),
+            intrinsics: self.intrinsics,
+            ground_truth: Some(Arc::new(self.ground_truth())),
+        }
+    }
+
+    fn top_distance(&self, brick: &BrickConfig) -> f32 {
+        self.config.table_distance - self.config.layer_height * brick.n_layers as f32
+    }
+
+    fn project(&self, x: f32, y: f32, z: f32) -> Point2<f32> {
+        let Intrinsics {
+            ppx, ppy, fx, fy, ..
+        } = self.intrinsics;
+        Point2::new(fx * x / z + ppx, fy * y / z + ppy)
+    }
+}
+
+/// The frame source that emits synthetic frames at fixed rate.
+#[derive(Debug)]
+pub struct SyntheticSource {
+    template: RealSenseMessage,
+    period: Duration,
+    start_time: Instant,
+    n_frames: u32,
+}
+
+impl SyntheticSource {
+    pub fn new(config: &SyntheticConfig) -> Fallible<Self> {
+        if !config.fps.is_finite() || config.fps <= 0.0 {
+            failure::bail!("fps must be positive, but get {}", config.fps);
+        }
+
+        // the scene is static, so we render it once
+        let scene = SyntheticScene::new(config.clone())?;
+        let template = scene.render(Duration::from_secs(0));
+
+        Ok(Self {
+            template,
+            period: Duration::from_secs_f64(1.0 / config.fps),
+            start_time: Instant::now(),
+            n_frames: 0,
+        })
+    }
+}
+
+#[async_trait]
+impl FrameSource for SyntheticSource {
+    async fn next_frame(&mut self) -> Fallible<Option<RealSenseMessage>> {
+        let timestamp = self.period * self.n_frames;
+        self.n_frames += 1;
+        tokio::time::delay_until(tokio::time::Instant::from_std(self.start_time + timestamp)).await;
+
+        let template = &self.template;
+        let msg = RealSenseMessage {
+            timestamp,
+            color_image: Arc::clone(&template.color_image),
+            depth_image: Arc::clone(&template.depth_image),
+            points: Arc::clone(&template.points),
+            texture_coordinates: Arc::clone(&template.texture_coordinates),
+            intrinsics: template.intrinsics,
+            ground_truth: template.ground_truth.clone(),
+        };
+        Ok(Some(msg))
+    }
+}
+
+// Computes the corners of the brick on the table plane.
+fn brick_corners(brick: &BrickConfig) -> [(f32, f32); 4] {
+    let BrickConfig {
+        size: [length, width],
+        position: [cx, cy],
+        angle,
+        ..
+    } = *brick;
+    let (sin, cos) = angle.to_radians().sin_cos();
+
+    // the y axis points downwards, so counter-clockwise rotation negates sin
+    let long_axis = (cos * length / 2.0, -sin * length / 2.0);
+    let short_axis = (sin * width / 2.0, cos * width / 2.0);
+
+    [
+        (
+            cx + long_axis.0 + short_axis.0,
+            cy + long_axis.1 + short_axis.1,
+        ),
+        (
+            cx - long_axis.0 + short_axis.0,
+            cy - long_axis.1 + short_axis.1,
+        ),
+        (
+            cx - long_axis.0 - short_axis.0,
+            cy - long_axis.1 - short_axis.1,
+        ),
+        (
+            cx + long_axis.0 - short_axis.0,
+            cy + long_axis.1 - short_axis.1,
+        ),
+    ]
+}
+
+// Converts the rotation of the long side to the detector convention, where
+// the detector turns the `min_area_rect` angle into the rotation of the short side.
+fn detector_angle(angle: f32) -> f32 {
+    90.0 - (-angle).rem_euclid(180.0)
+}
+
+// Checks if the point on the table plane lies on the brick.
+fn brick_contains(brick: &BrickConfig, x: f32, y: f32) -> bool {
+    let BrickConfig {
+        size: [length, width],
+        position: [cx, cy],
+        angle,
+        ..
+    } = *brick;
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let dx = x - cx;
+    let dy = y - cy;
+    let along = dx * cos - dy * sin;
+    let across = dx * sin + dy * cos;
+    along.abs() <= length / 2.0 && across.abs() <= width / 2.0
+}
+
+// Converts OpenCV HSV, where hue ranges in [0, 180), to RGB.
+fn hsv_to_rgb([h, s, v]: [u8; 3]) -> [u8; 3] {
+    let h = (h as f32 * 2.0) % 360.0;
+    let s = s as f32 / 255.0;
+    let v = v as f32 / 255.0;
+
+    let chroma = v * s;
+    let sector = h / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let offset = v - chroma;
+    let to_u8 = |value: f32| ((value + offset) * 255.0).round() as u8;
+
+    [to_u8(r), to_u8(g), to_u8(b)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ControllerConfig, controller::pixel_to_robot, utils::HackyTryFrom};
+    use hacky_arm_common::opencv::prelude::*;
+    use hacky_detection::{DetectOptions, Detector};
+
+    fn scene(angle: f32) -> SyntheticScene {
+        let config = SyntheticConfig {
+            width: 640,
+            height: 480,
+            fps: 30.0,
+            fov: 69.4,
+            table_distance: 0.26,
+            table_color: [100, 30, 200],
+            layer_height: 0.0095,
+            bricks: vec![BrickConfig {
+                color: [5, 200, 200],
+                size: [0.032, 0.016],
+                position: [0.02, -0.01],
+                angle,
+                n_layers: 3,
+            }],
+        };
+        SyntheticScene::new(config).unwrap()
+    }
+
+    #[test]
+    fn hsv_conversion() {
+        assert_eq!(hsv_to_rgb([0, 255, 255]), [255, 0, 0]);
+        assert_eq!(hsv_to_rgb([60, 255, 255]), [0, 255, 0]);
+        assert_eq!(hsv_to_rgb([120, 255, 255]), [0, 0, 255]);
+        assert_eq!(hsv_to_rgb([77, 0, 128]), [128, 128, 128]);
+    }
+
+    #[test]
+    fn rendered_depth_matches_ground_truth() -> Fallible<()> {
+        let scene = scene(30.0);
+        let msg = scene.render(Duration::from_secs(0));
+        let truth = &msg.ground_truth.as_ref().unwrap()[0];
+
+        let distance = msg
+            .depth_image
+            .distance(truth.x.round() as usize, truth.y.round() as usize)?;
+        assert!((distance - truth.depth).abs() < 1e-3);
+
+        let table = msg.depth_image.distance(0, 0)?;
+        assert!((table - 0.26).abs() < 1e-3);
+        Ok(())
+    }
+
+    #[test]
+    fn detector_finds_synthetic_brick() -> Fallible<()> {
+        let detector = Detector {
+            lower_bound: [0, 150, 150],
+            upper_bound: [10, 255, 255],
+            blur_kernel: 3,
+            min_arc_length: 50.0,
+            draw_position: false,
+            ..Default::default()
+        };
+
+        // avoid multiples of 90 degrees, where mirrored conventions agree
+        for &angle in [-60.0, -20.0, 30.0, 75.0].iter() {
+            let scene = scene(angle);
+            let msg = scene.render(Duration::from_secs(0));
+            let truth = &msg.ground_truth.as_ref().unwrap()[0];
+
+            let mat: Mat = HackyTryFrom::try_from(&*msg.color_image)?;
+            let objects = detector.detect(&mat, &DetectOptions::default())?.objects;
+
+            assert_eq!(objects.len(), 1);
+            let obj = &objects[0];
+            assert!((obj.x as f32 - truth.x).abs() <= 3.0);
+            assert!((obj.y as f32 - truth.y).abs() <= 3.0);
+            // rectangles are symmetric under half turns
+            let angle_error = ((obj.angle - truth.angle + 90.0).rem_euclid(180.0) - 90.0).abs();
+            assert!(
+                angle_error <= 2.0,
+                "detected angle {} != {}",
+                obj.angle,
+                truth.angle
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn controller_maps_synthetic_brick() -> Fallible<()> {
+        let scene = scene(30.0);
+        let msg = scene.render(Duration::from_secs(0));
+        let truth = &msg.ground_truth.as_ref().unwrap()[0];
+        let Intrinsics {
+            ppx, ppy, fx, fy, ..
+        } = msg.intrinsics;
+
+        // the robot x and y axes run along the camera y and x axes in millimeters,
+        // calibrated on the top surface of the brick
+        let scale_x = (truth.depth * 1000.0 / fx) as f64;
+        let scale_y = (truth.depth * 1000.0 / fy) as f64;
+        let config: ControllerConfig = json5::from_str(&format!(
+            r#"{{
+                "linear_transform": [[0.0, {}], [{}, 0.0]],
+                "translation": [{}, {}],
+                "depth_image": [0.25, 0.26],
+                "depth_robot": [-30.0, -40.0],
+            }}"#,
+            scale_y,
+            scale_x,
+            -ppy as f64 * scale_y,
+            -ppx as f64 * scale_x
+        ))?;
+
+        let detector = Detector {
+            lower_bound: [0, 150, 150],
+            upper_bound: [10, 255, 255],
+            blur_kernel: 3,
+            min_arc_length: 50.0,
+            draw_position: false,
+            ..Default::default()
+        };
+        let mat: Mat = HackyTryFrom::try_from(&*msg.color_image)?;
+        let objects = detector.detect(&mat, &DetectOptions::default())?.objects;
+        assert_eq!(objects.len(), 1);
+
+        let (x, y) = pixel_to_robot(&config, objects[0].x, objects[0].y);
+        assert!((x - truth.position.y * 1000.0).abs() <= 2.0);
+        assert!((y - truth.position.x * 1000.0).abs() <= 2.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{config::TrackerConfig, object_detector::Object};
+use geo::LineString;
+
+/// The pose of an object in image pixels, with depth in meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub x: f32,
+    pub y: f32,
+    /// angle in degrees
+    pub angle: f32,
+    pub depth: f32,
+}
+
+impl Pose {
+    pub fn of(object: &Object) -> Self {
+        Self {
+            x: object.x as f32,
+            y: object.y as f32,
+            angle: object.angle,
+            depth: object.depth,
+        }
+    }
+
+    // Blends the pose toward the other one by the ratio. Angles are
+    // blended modulo 180 degrees since bricks are symmetric.
+    fn blend(&self, other: &Pose, ratio: f32) -> Self {
+        Self {
+            x: self.x + (other.x - self.x) * ratio,
+            y: self.y + (other.y - self.y) * ratio,
+            angle: self.angle + angle_difference(other.angle, self.angle) * ratio,
+            depth: self.depth + (other.depth - self.depth) * ratio,
+        }
+    }
+}
+
+/// The difference of angles in degrees wrapped into [-90, 90).
+pub fn angle_difference(lhs: f32, rhs: f32) -> f32 {
+    (lhs - rhs + 90.0).rem_euclid(180.0) - 90.0
+}
+
+#[derive(Debug, Clone)]
+struct Track {
+    id: u64,
+    label: String,
+    age: usize,
+    n_misses: usize,
+    pose: Pose,
+    bbox: Option<BoundingBox>,
+}
+
+/// The tracker that associates objects across frames.
+///
+/// Objects are matched to existing tracks of the same label by bounding
+/// box overlap or center distance. Matched objects inherit the track ID
+/// and get the smoothed pose, while unmatched ones start new tracks.
+#[derive(Debug)]
+pub struct Tracker {
+    config: TrackerConfig,
+    tracks: Vec<Track>,
+    next_id: u64,
+}
+
+impl Tracker {
+    pub fn new(config: TrackerConfig) -> Self {
+        Self {
+            config,
+            tracks: vec![],
+            next_id: 1,
+        }
+    }
+
+    /// Associates the objects of a new frame with tracks, and fills in the
+    /// track fields of the objects.
+    pub fn update(&mut self, objects: &mut [Object]) {
+        let TrackerConfig {
+            min_overlap,
+            max_distance,
+            max_misses,
+            smoothing,
+        } = self.config;
+
+        let bboxes = objects
+            .iter()
+            .map(|object| bounding_box(&object.polygon))
+            .collect::<Vec<_>>();
+
+        // collect candidate pairs and match greedily from the closest
+        let mut candidates = vec![];
+        for (track_index, track) in self.tracks.iter().enumerate() {
+            for (object_index, object) in objects.iter().enumerate() {
+                if track.label != object.label {
+                    continue;
+                }
+                let pose = Pose::of(object);
+                let distance = (pose.x - track.pose.x).hypot(pose.y - track.pose.y);
+                let overlap = match (&track.bbox, &bboxes[object_index]) {
+                    (Some(lhs), Some(rhs)) => overlap_ratio(lhs, rhs),
+                    _ => 0.0,
+                };
+                if overlap >= min_overlap || distance <= max_distance {
+                    candidates.push((distance, track_index, object_index));
+                }
+            }
+        }
+        candidates.sort_by(|(lhs, _, _), (rhs, _, _)| lhs.partial_cmp(rhs).unwrap());
+
+        let mut track_matched = vec![false; self.tracks.len()];
+        let mut object_matched = vec![false; objects.len()];
+
+        for (_, track_index, object_index) in candidates {
+            if track_matched[track_index] || object_matched[object_index] {
+                continue;
+            }
+            track_matched[track_index] = true;
+            object_matched[object_index] = true;
+
+            let track = &mut self.tracks[track_index];
+            let object = &mut objects[object_index];
+            track.age += 1;
+            track.n_misses = 0;
+            track.pose = track.pose.blend(&Pose::of(object), smoothing);
+            track.bbox = bboxes[object_index];
+
+            object.track_id = track.id;
+            object.track_age = track.age;
+            object.smoothed_pose = track.pose;
+        }
+
+        // age out unmatched tracks
+        for (track, matched) in self.tracks.iter_mut().zip(track_matched) {
+            if !matched {
+                track.n_misses += 1;
+            }
+        }
+        self.tracks.retain(|track| track.n_misses <= max_misses);
+
+        // start new tracks
+        for (index, object) in objects.iter_mut().enumerate() {
+            if object_matched[index] {
+                continue;
+            }
+            let track = Track {
+                id: self.next_id,
+                label: object.label.clone(),
+                age: 1,
+                n_misses: 0,
+                pose: Pose::of(object),
+                bbox: bboxes[index],
+            };
+            self.next_id += 1;
+
+            object.track_id = track.id;
+            object.track_age = track.age;
+            object.smoothed_pose = track.pose;
+            self.tracks.push(track);
+        }
+    }
+}
+
+/// The axis-aligned bounding box in pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BoundingBox {
+    min_x: f32,
+    min_y: f32,
+    max_x: f32,
+    max_y: f32,
+}
+
+impl BoundingBox {
+    fn area(&self) -> f32 {
+        (self.max_x - self.min_x) * (self.max_y - self.min_y)
+    }
+}
+
+fn bounding_box(polygon: &LineString<f32>) -> Option<BoundingBox> {
+    let mut coords = polygon.0.iter();
+    let first = coords.next()?;
+    let init = BoundingBox {
+        min_x: first.x,
+        min_y: first.y,
+        max_x: first.x,
+        max_y: first.y,
+    };
+    Some(coords.fold(init, |bbox, coord| BoundingBox {
+        min_x: bbox.min_x.min(coord.x),
+        min_y: bbox.min_y.min(coord.y),
+        max_x: bbox.max_x.max(coord.x),
+        max_y: bbox.max_y.max(coord.y),
+    }))
+}
+
+// Computes the intersection over union of bounding boxes.
+fn overlap_ratio(lhs: &BoundingBox, rhs: &BoundingBox) -> f32 {
+    let width = lhs.max_x.min(rhs.max_x) - lhs.min_x.max(rhs.min_x);
+    let height = lhs.max_y.min(rhs.max_y) - lhs.min_y.max(rhs.min_y);
+    if width <= 0.0 || height <= 0.0 {
+        return 0.0;
+    }
+    let intersection = width * height;
+    intersection / (lhs.area() + rhs.area() - intersection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_detector::object_at;
+
+    #[test]
+    fn keep_track_ids_across_frames() {
+        let mut tracker = Tracker::new(TrackerConfig::default());
+
+        let mut frame = vec![object_at(100, 100, 10.0), object_at(300, 200, -80.0)];
+        tracker.update(&mut frame);
+        let ids = (frame[0].track_id, frame[1].track_id);
+        assert_ne!(ids.0, ids.1);
+
+        // objects swap order and move slightly, the angle wraps around
+        let mut frame = vec![object_at(302, 201, 88.0), object_at(103, 99, 12.0)];
+        tracker.update(&mut frame);
+        assert_eq!(frame[0].track_id, ids.1);
+        assert_eq!(frame[1].track_id, ids.0);
+        assert_eq!(frame[0].track_age, 2);
+        assert!(angle_difference(frame[0].smoothed_pose.angle, -86.0).abs() < 1e-3);
+
+        // a far object starts a new track
+        let mut frame = vec![object_at(500, 400, 0.0)];
+        tracker.update(&mut frame);
+        assert!(frame[0].track_id != ids.0 && frame[0].track_id != ids.1);
+        assert_eq!(frame[0].track_age, 1);
+    }
+}
@@ -4,7 +4,7 @@ use hacky_arm_common::opencv::{
     imgproc,
     prelude::*,
 };
-use image::{Bgr, Bgra, Luma, Rgb, Rgba};
+use image::{Bgr, Bgra, GrayImage, ImageBuffer, Luma, Rgb, RgbImage, Rgba};
 use realsense_rust::Rs2Image;
 use std::{
     ops::{Deref, DerefMut},
@@ -196,6 +196,47 @@ impl<'a> HackyTryFrom<Rs2Image<'a>> for Mat {
     }
 }
 
+impl HackyTryFrom<&RgbImage> for Mat {
+    type Error = failure::Error;
+
+    fn try_from(from: &RgbImage) -> Fallible<Self> {
+        let pixel_iter = from.pixels().map(|pixel| {
+            let Rgb(samples) = *pixel;
+            Vec3b::from(samples)
+        });
+        let mat = Mat::from_exact_iter(pixel_iter)?.reshape(3, from.height() as i32)?;
+        let mut out = Mat::default()?;
+        imgproc::cvt_color(&mat, &mut out, imgproc::COLOR_RGB2BGR, 0)?;
+        Ok(out)
+    }
+}
+
+impl HackyTryFrom<&ImageBuffer<Luma<u16>, Vec<u16>>> for Mat {
+    type Error = failure::Error;
+
+    fn try_from(from: &ImageBuffer<Luma<u16>, Vec<u16>>) -> Fallible<Self> {
+        let pixel_iter = from.pixels().map(|pixel| {
+            let Luma([sample]) = *pixel;
+            sample
+        });
+        let mat = Mat::from_exact_iter(pixel_iter)?.reshape(1, from.height() as i32)?;
+        Ok(mat)
+    }
+}
+
+impl HackyTryFrom<&GrayImage> for Mat {
+    type Error = failure::Error;
+
+    fn try_from(from: &GrayImage) -> Fallible<Self> {
+        let pixel_iter = from.pixels().map(|pixel| {
+            let Luma([sample]) = *pixel;
+            sample
+        });
+        let mat = Mat::from_exact_iter(pixel_iter)?.reshape(1, from.height() as i32)?;
+        Ok(mat)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,1031 @@
+use crate::config::{
+    BrickConfig, Config, ControllerConfig, DepthCameraConfig, DepthFilterConfig, DepthModelConfig,
+    DepthSegmentationConfig, DepthStatistic, FrameSourceConfig, GripperConfig,
+    ObjectDetectorConfig, PlaybackConfig, RealSenseConfig, StabilityConfig, SyntheticConfig,
+    TrackerConfig, VideoCameraConfig,
+};
+use hacky_detection::{
+    ColorClass, DetectorParams, Morphology, Normalization, ObjectFilter, Regions, ShapeClassifier,
+    ShapeTemplate, Split, Stage,
+};
+use realsense_rust::kind::Format;
+use std::{
+    collections::HashSet,
+    fmt::{self, Display, Formatter},
+};
+
+/// The severity of a configuration issue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// the configuration cannot be used
+    Error,
+    /// the configuration is usable, but likely not what is intended
+    Warning,
+}
+
+/// A semantic problem found in the configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// the path to the offending field, e.g. "controller.depth_image[3]"
+    pub path: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.path, self.message)
+    }
+}
+
+/// The collector of configuration issues.
+#[derive(Debug, Default)]
+struct Validator {
+    issues: Vec<ConfigIssue>,
+}
+
+impl Validator {
+    fn report<P, M>(&mut self, path: P, message: M)
+    where
+        P: Into<String>,
+        M: Into<String>,
+    {
+        self.issues.push(ConfigIssue {
+            severity: Severity::Error,
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn warn<P, M>(&mut self, path: P, message: M)
+    where
+        P: Into<String>,
+        M: Into<String>,
+    {
+        self.issues.push(ConfigIssue {
+            severity: Severity::Warning,
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    fn check<P, M>(&mut self, ok: bool, path: P, message: M)
+    where
+        P: Into<String>,
+        M: Into<String>,
+    {
+        if !ok {
+            self.report(path, message);
+        }
+    }
+
+    fn check_warn<P, M>(&mut self, ok: bool, path: P, message: M)
+    where
+        P: Into<String>,
+        M: Into<String>,
+    {
+        if !ok {
+            self.warn(path, message);
+        }
+    }
+}
+
+impl Config {
+    /// Checks the semantics of the configuration and reports all problems found.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut validator = Validator::default();
+        validate_realsense(&mut validator, &self.realsense);
+        validate_object_detector(&mut validator, &self.object_detector);
+        validate_controller(&mut validator, &self.controller);
+        validate_tracker(&mut validator, &self.tracker);
+        validator.issues
+    }
+}
+
+impl ObjectDetectorConfig {
+    /// Checks the semantics of the object detector parameters.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut validator = Validator::default();
+        validate_object_detector(&mut validator, self);
+        validator.issues
+    }
+}
+
+fn validate_realsense(validator: &mut Validator, config: &RealSenseConfig) {
+    let RealSenseConfig {
+        source,
+        depth_filters,
+        depth_camera,
+        video_camera,
+    } = config;
+
+    match source {
+        FrameSourceConfig::Device => {}
+        FrameSourceConfig::Playback(PlaybackConfig { dir, speed, .. }) => {
+            validator.check(
+                dir.is_dir(),
+                "realsense.source.dir",
+                format!("{} is not a directory", dir.display()),
+            );
+            validator.check(
+                speed.is_finite() && *speed > 0.0,
+                "realsense.source.speed",
+                format!("must be positive, but get {}", speed),
+            );
+        }
+        FrameSourceConfig::Synthetic(synthetic) => {
+            validate_synthetic(validator, synthetic);
+        }
+    }
+
+    for (index, filter) in depth_filters.iter().enumerate() {
+        let path = format!("realsense.depth_filters[{}]", index);
+        match *filter {
+            DepthFilterConfig::Decimation { factor } => {
+                validator.check(factor >= 1, format!("{}.factor", path), "must be positive");
+            }
+            DepthFilterConfig::Spatial { alpha, delta, .. }
+            | DepthFilterConfig::Temporal { alpha, delta } => {
+                validator.check(
+                    alpha > 0.0 && alpha <= 1.0,
+                    format!("{}.alpha", path),
+                    format!("must be in range (0, 1], but get {}", alpha),
+                );
+                validator.check(
+                    delta > 0.0,
+                    format!("{}.delta", path),
+                    format!("must be positive, but get {}", delta),
+                );
+            }
+            DepthFilterConfig::HoleFilling { .. } => {}
+        }
+    }
+
+    {
+        let DepthCameraConfig {
+            width, fps, format, ..
+        } = depth_camera;
+        validator.check(
+            *width > 0,
+            "realsense.depth_camera.width",
+            "must be positive",
+        );
+        validator.check(*fps > 0, "realsense.depth_camera.fps", "must be positive");
+        validator.check(
+            matches!(format, Format::Z16),
+            "realsense.depth_camera.format",
+            "depth camera only supports Z16 format",
+        );
+    }
+
+    {
+        let VideoCameraConfig {
+            width, fps, format, ..
+        } = video_camera;
+        validator.check(
+            *width > 0,
+            "realsense.video_camera.width",
+            "must be positive",
+        );
+        validator.check(*fps > 0, "realsense.video_camera.fps", "must be positive");
+        validator.check(
+            !matches!(format, Format::Z16),
+            "realsense.video_camera.format",
+            "video camera does not support Z16 format",
+        );
+    }
+}
+
+fn validate_synthetic(validator: &mut Validator, config: &SyntheticConfig) {
+    let SyntheticConfig {
+        width,
+        height,
+        fps,
+        fov,
+        table_distance,
+        table_color,
+        layer_height,
+        bricks,
+    } = config;
+
+    validator.check(*width > 0, "realsense.source.width", "must be positive");
+    validator.check(*height > 0, "realsense.source.height", "must be positive");
+    validator.check(
+        fps.is_finite() && *fps > 0.0,
+        "realsense.source.fps",
+        format!("must be positive, but get {}", fps),
+    );
+    validator.check(
+        *fov > 0.0 && *fov < 180.0,
+        "realsense.source.fov",
+        format!("must be in range (0, 180), but get {}", fov),
+    );
+    validator.check(
+        *table_distance > 0.0,
+        "realsense.source.table_distance",
+        "must be positive",
+    );
+    validator.check(
+        *layer_height > 0.0,
+        "realsense.source.layer_height",
+        "must be positive",
+    );
+    validate_hsv(
+        validator,
+        "realsense.source.table_color",
+        &to_i32(table_color),
+    );
+
+    for (index, brick) in bricks.iter().enumerate() {
+        let BrickConfig {
+            color,
+            size: [length, width],
+            n_layers,
+            ..
+        } = brick;
+        let path = format!("realsense.source.bricks[{}]", index);
+
+        validate_hsv(validator, &format!("{}.color", path), &to_i32(color));
+        validator.check(
+            *length > 0.0 && *width > 0.0,
+            format!("{}.size", path),
+            "must be positive",
+        );
+        validator.check(
+            *table_distance - *layer_height * *n_layers as f32 > 0.0,
+            format!("{}.n_layers", path),
+            "the stack is taller than the camera height",
+        );
+    }
+}
+
+fn validate_object_detector(validator: &mut Validator, config: &ObjectDetectorConfig) {
+    let ObjectDetectorConfig {
+        params_file,
+        detector:
+            DetectorParams {
+                blur_kernel,
+                n_dilations,
+                dilation_kernel,
+                n_erosions,
+                erosion_kernel,
+                min_arc_length,
+                max_arc_length,
+                roi,
+                lower_bound,
+                upper_bound,
+                normalization,
+                pipeline,
+                classes,
+                filter,
+                regions,
+                split,
+                shapes,
+                ..
+            },
+        depth_statistic,
+        depth_segmentation,
+        ..
+    } = config;
+
+    // tell which parameter file the issue comes from
+    let prefix = match params_file {
+        Some(path) => format!("object_detector({})", path.display()),
+        None => String::from("object_detector"),
+    };
+    let prefix = prefix.as_str();
+
+    let check_kernel = |validator: &mut Validator, name: &str, kernel: &Option<i32>| {
+        if let Some(kernel) = kernel {
+            validate_kernel(validator, &format!("{}.{}", prefix, name), *kernel);
+        }
+    };
+    check_kernel(validator, "blur_kernel", blur_kernel);
+    check_kernel(validator, "dilation_kernel", dilation_kernel);
+    check_kernel(validator, "erosion_kernel", erosion_kernel);
+
+    if let Some(n_dilations) = n_dilations {
+        validator.check(
+            *n_dilations >= 0,
+            format!("{}.n_dilations", prefix),
+            "must not be negative",
+        );
+    }
+    if let Some(n_erosions) = n_erosions {
+        validator.check(
+            *n_erosions >= 0,
+            format!("{}.n_erosions", prefix),
+            "must not be negative",
+        );
+    }
+
+    if let Some(min_arc_length) = min_arc_length {
+        validator.check(
+            *min_arc_length >= 0.0,
+            format!("{}.min_arc_length", prefix),
+            "must not be negative",
+        );
+    }
+    if let (Some(min_arc_length), Some(max_arc_length)) = (min_arc_length, max_arc_length) {
+        validator.check(
+            min_arc_length <= max_arc_length,
+            format!("{}.min_arc_length", prefix),
+            format!(
+                "must not exceed max_arc_length, but get {} > {}",
+                min_arc_length, max_arc_length
+            ),
+        );
+    }
+
+    if let Some(roi) = roi {
+        for (index, ratio) in roi.iter().enumerate() {
+            validator.check(
+                *ratio > 0.0 && *ratio <= 1.0,
+                format!("{}.roi[{}]", prefix, index),
+                format!("must be in range (0, 1], but get {}", ratio),
+            );
+        }
+    }
+
+    match (lower_bound, upper_bound) {
+        (Some(lower_bound), Some(upper_bound)) => {
+            validate_hsv_range(validator, prefix, lower_bound, upper_bound);
+        }
+        (Some(lower_bound), None) => {
+            validate_hsv(validator, &format!("{}.lower_bound", prefix), lower_bound);
+        }
+        (None, Some(upper_bound)) => {
+            validate_hsv(validator, &format!("{}.upper_bound", prefix), upper_bound);
+        }
+        (None, None) => {}
+    }
+
+    match depth_statistic {
+        Some(DepthStatistic::TrimmedMean { ratio }) => {
+            validator.check(
+                *ratio >= 0.0 && *ratio < 0.5,
+                format!("{}.depth_statistic.ratio", prefix),
+                format!("must be in range [0, 0.5), but get {}", ratio),
+            );
+        }
+        Some(DepthStatistic::Percentile { percentile }) => {
+            validator.check(
+                *percentile >= 0.0 && *percentile <= 100.0,
+                format!("{}.depth_statistic.percentile", prefix),
+                format!("must be in range [0, 100], but get {}", percentile),
+            );
+        }
+        Some(DepthStatistic::Median) | None => {}
+    }
+
+    if let Some(normalization) = normalization {
+        for (index, step) in normalization.iter().enumerate() {
+            let path = format!("{}.normalization[{}]", prefix, index);
+            match step {
+                Normalization::GrayWorld => {}
+                Normalization::WhiteReference {
+                    region: [x, y, width, height],
+                } => {
+                    validator.check(
+                        *x >= 0 && *y >= 0,
+                        format!("{}.region", path),
+                        format!("must not start at negative pixels, but get ({}, {})", x, y),
+                    );
+                    validator.check(
+                        *width > 0 && *height > 0,
+                        format!("{}.region", path),
+                        format!("must have a positive size, but get {}x{}", width, height),
+                    );
+                }
+                Normalization::Clahe {
+                    clip_limit,
+                    tile_size,
+                } => {
+                    validator.check(
+                        *clip_limit > 0.0,
+                        format!("{}.clip_limit", path),
+                        format!("must be positive, but get {}", clip_limit),
+                    );
+                    validator.check(
+                        *tile_size >= 1,
+                        format!("{}.tile_size", path),
+                        "must be positive",
+                    );
+                }
+                Normalization::Gamma { gamma } => {
+                    validator.check(
+                        gamma.is_finite() && *gamma > 0.0,
+                        format!("{}.gamma", path),
+                        format!("must be positive, but get {}", gamma),
+                    );
+                }
+            }
+        }
+    }
+
+    if let Some(pipeline) = pipeline {
+        validate_pipeline(validator, &format!("{}.pipeline", prefix), pipeline);
+    }
+
+    if let Some(classes) = classes {
+        let mut labels = HashSet::new();
+        for (index, class) in classes.iter().enumerate() {
+            let ColorClass {
+                label,
+                lower_bound,
+                upper_bound,
+                pipeline,
+                ..
+            } = class;
+            let path = format!("{}.classes[{}]", prefix, index);
+
+            validator.check(
+                !label.is_empty(),
+                format!("{}.label", path),
+                "must not be empty",
+            );
+            validator.check(
+                labels.insert(label),
+                format!("{}.label", path),
+                format!("duplicated label {:?}", label),
+            );
+            validate_hsv_range(validator, &path, lower_bound, upper_bound);
+            if let Some(pipeline) = pipeline {
+                validate_pipeline(validator, &format!("{}.pipeline", path), pipeline);
+            }
+        }
+    }
+
+    if let Some(depth_segmentation) = depth_segmentation {
+        let DepthSegmentationConfig {
+            label,
+            min_height,
+            max_height,
+            plane_distance,
+            n_iterations,
+            sample_stride,
+            ..
+        } = depth_segmentation;
+        let path = format!("{}.depth_segmentation", prefix);
+
+        validator.check(
+            !label.is_empty(),
+            format!("{}.label", path),
+            "must not be empty",
+        );
+        validator.check(
+            *min_height >= 0.0,
+            format!("{}.min_height", path),
+            format!("must not be negative, but get {}", min_height),
+        );
+        if let Some(max_height) = max_height {
+            validator.check(
+                max_height >= min_height,
+                format!("{}.max_height", path),
+                format!(
+                    "must not be less than min_height, but get {} < {}",
+                    max_height, min_height
+                ),
+            );
+        }
+        validator.check(
+            *plane_distance > 0.0,
+            format!("{}.plane_distance", path),
+            format!("must be positive, but get {}", plane_distance),
+        );
+        validator.check(
+            *n_iterations > 0,
+            format!("{}.n_iterations", path),
+            "must be positive",
+        );
+        validator.check(
+            *sample_stride > 0,
+            format!("{}.sample_stride", path),
+            "must be positive",
+        );
+    }
+
+    if let Some(filter) = filter {
+        validate_object_filter(validator, &format!("{}.filter", prefix), filter);
+    }
+
+    if let Some(Split {
+        distance_ratio,
+        background_iterations,
+    }) = split
+    {
+        validator.check(
+            *distance_ratio > 0.0 && *distance_ratio < 1.0,
+            format!("{}.split.distance_ratio", prefix),
+            format!("must be in range (0, 1), but get {}", distance_ratio),
+        );
+        validator.check(
+            *background_iterations >= 1,
+            format!("{}.split.background_iterations", prefix),
+            "must be positive",
+        );
+    }
+
+    if let Some(ShapeClassifier {
+        templates,
+        max_distance,
+        reject_unknown,
+        ..
+    }) = shapes
+    {
+        let path = format!("{}.shapes", prefix);
+        validator.check(
+            !templates.is_empty() || !reject_unknown,
+            format!("{}.templates", path),
+            "must not be empty to reject unknown shapes",
+        );
+        for (index, ShapeTemplate { label, contour }) in templates.iter().enumerate() {
+            let path = format!("{}.templates[{}]", path, index);
+            validator.check(
+                !label.is_empty(),
+                format!("{}.label", path),
+                "must not be empty",
+            );
+            validator.check(
+                contour.len() >= 3,
+                format!("{}.contour", path),
+                format!("expect at least 3 points, but get {}", contour.len()),
+            );
+        }
+        if let Some(max_distance) = max_distance {
+            validator.check(
+                *max_distance >= 0.0,
+                format!("{}.max_distance", path),
+                format!("must not be negative, but get {}", max_distance),
+            );
+        }
+    }
+
+    if let Some(Regions { include, exclude }) = regions {
+        for (name, polygons) in &[("include", include), ("exclude", exclude)] {
+            for (index, vertices) in polygons.iter().enumerate() {
+                let path = format!("{}.regions.{}[{}]", prefix, name, index);
+                validator.check(
+                    vertices.len() >= 3,
+                    &path,
+                    format!("expect at least 3 vertices, but get {}", vertices.len()),
+                );
+                validator.check(
+                    vertices.iter().flatten().all(|value| value.is_finite()),
+                    &path,
+                    "vertices must be finite numbers",
+                );
+            }
+        }
+    }
+}
+
+fn validate_pipeline(validator: &mut Validator, path: &str, pipeline: &[Stage]) {
+    let n_thresholds = pipeline
+        .iter()
+        .filter(|stage| matches!(stage, Stage::HsvThreshold { .. }))
+        .count();
+    validator.check(
+        n_thresholds == 1,
+        path,
+        format!(
+            "must have exactly one hsv_threshold stage, but get {}",
+            n_thresholds
+        ),
+    );
+
+    let check_kernel = |validator: &mut Validator, path: &str, kernel: i32| {
+        validate_kernel(validator, &format!("{}.kernel", path), kernel);
+    };
+
+    for (index, stage) in pipeline.iter().enumerate() {
+        let path = format!("{}[{}]", path, index);
+        match stage {
+            Stage::HsvThreshold {
+                lower_bound,
+                upper_bound,
+            } => {
+                if let Some(lower_bound) = lower_bound {
+                    validate_hsv(validator, &format!("{}.lower_bound", path), lower_bound);
+                }
+                if let Some(upper_bound) = upper_bound {
+                    validate_hsv(validator, &format!("{}.upper_bound", path), upper_bound);
+                }
+            }
+            Stage::MedianBlur { kernel } => {
+                check_kernel(validator, &path, *kernel);
+            }
+            Stage::GaussianBlur { kernel, sigma } => {
+                check_kernel(validator, &path, *kernel);
+                validator.check(
+                    *sigma >= 0.0,
+                    format!("{}.sigma", path),
+                    "must not be negative",
+                );
+            }
+            Stage::Invert => {}
+            Stage::Dilate(morphology)
+            | Stage::Erode(morphology)
+            | Stage::Open(morphology)
+            | Stage::Close(morphology) => {
+                let Morphology {
+                    kernel, iterations, ..
+                } = morphology;
+                check_kernel(validator, &path, *kernel);
+                validator.check(
+                    *iterations >= 0,
+                    format!("{}.iterations", path),
+                    "must not be negative",
+                );
+            }
+        }
+    }
+}
+
+fn validate_controller(validator: &mut Validator, config: &ControllerConfig) {
+    let ControllerConfig {
+        linear_transform,
+        translation,
+        depth_image,
+        depth_robot,
+        depth_model,
+        z_limits,
+        max_extrapolation,
+        stability,
+        gripper,
+        ..
+    } = config;
+
+    for (row, values) in linear_transform.iter().enumerate() {
+        for (col, value) in values.iter().enumerate() {
+            validator.check(
+                value.is_finite(),
+                format!("controller.linear_transform[{}][{}]", row, col),
+                "must be a finite number",
+            );
+        }
+    }
+    for (index, value) in translation.iter().enumerate() {
+        validator.check(
+            value.is_finite(),
+            format!("controller.translation[{}]", index),
+            "must be a finite number",
+        );
+    }
+
+    validator.check(
+        !depth_image.is_empty(),
+        "controller.depth_image",
+        "must not be empty",
+    );
+    validator.check(
+        depth_image.len() == depth_robot.len(),
+        "controller.depth_robot",
+        format!(
+            "expect {} entries to match depth_image, but get {}",
+            depth_image.len(),
+            depth_robot.len()
+        ),
+    );
+    for (index, depth) in depth_image.iter().enumerate() {
+        validator.check(
+            *depth > 0.0,
+            format!("controller.depth_image[{}]", index),
+            format!("must be positive, but get {}", depth),
+        );
+    }
+    for (index, pair) in depth_image.windows(2).enumerate() {
+        validator.check(
+            pair[0] > pair[1],
+            format!("controller.depth_image[{}]", index + 1),
+            format!(
+                "the table must be strictly decreasing, but get {} after {}",
+                pair[1], pair[0]
+            ),
+        );
+    }
+
+    if let DepthModelConfig::Polynomial { degree } = depth_model {
+        validator.check(
+            *degree < depth_image.len(),
+            "controller.depth_model.degree",
+            format!(
+                "requires more than {} entries in depth_image, but get {}",
+                degree,
+                depth_image.len()
+            ),
+        );
+    }
+    if let Some([min_z, max_z]) = z_limits {
+        validator.check(
+            min_z <= max_z,
+            "controller.z_limits",
+            format!("the lower limit exceeds the upper, {} > {}", min_z, max_z),
+        );
+    }
+    validator.check(
+        *max_extrapolation >= 0.0,
+        "controller.max_extrapolation",
+        format!("must not be negative, but get {}", max_extrapolation),
+    );
+
+    let StabilityConfig {
+        n_frames,
+        max_position_deviation,
+        max_angle_deviation,
+        max_depth_deviation,
+    } = stability;
+    validator.check(
+        *n_frames >= 1,
+        "controller.stability.n_frames",
+        "must be at least 1",
+    );
+    for (name, value) in &[
+        ("max_position_deviation", max_position_deviation),
+        ("max_angle_deviation", max_angle_deviation),
+        ("max_depth_deviation", max_depth_deviation),
+    ] {
+        validator.check(
+            **value >= 0.0,
+            format!("controller.stability.{}", name),
+            format!("must not be negative, but get {}", value),
+        );
+    }
+
+    let GripperConfig {
+        joint4_limits: [min_joint4, max_joint4],
+        max_opening,
+    } = gripper;
+    validator.check(
+        min_joint4 < max_joint4,
+        "controller.gripper.joint4_limits",
+        format!(
+            "the lower limit must be less than the upper, but get {} >= {}",
+            min_joint4, max_joint4
+        ),
+    );
+    if let Some(max_opening) = max_opening {
+        validator.check(
+            *max_opening > 0.0,
+            "controller.gripper.max_opening",
+            format!("must be positive, but get {}", max_opening),
+        );
+    }
+}
+
+fn validate_tracker(validator: &mut Validator, config: &TrackerConfig) {
+    let TrackerConfig {
+        min_overlap,
+        max_distance,
+        smoothing,
+        ..
+    } = config;
+
+    validator.check(
+        *min_overlap >= 0.0 && *min_overlap <= 1.0,
+        "tracker.min_overlap",
+        format!("must be in range [0, 1], but get {}", min_overlap),
+    );
+    validator.check(
+        *max_distance >= 0.0,
+        "tracker.max_distance",
+        format!("must not be negative, but get {}", max_distance),
+    );
+    validator.check(
+        *smoothing > 0.0 && *smoothing <= 1.0,
+        "tracker.smoothing",
+        format!("must be in range (0, 1], but get {}", smoothing),
+    );
+}
+
+fn validate_object_filter(validator: &mut Validator, path: &str, filter: &ObjectFilter) {
+    let ObjectFilter {
+        min_area,
+        max_area,
+        min_aspect_ratio,
+        max_aspect_ratio,
+        min_rectangularity,
+        min_solidity,
+        min_mean_hsv,
+        max_mean_hsv,
+    } = filter;
+
+    if let Some(min_area) = min_area {
+        validator.check(
+            *min_area >= 0.0,
+            format!("{}.min_area", path),
+            format!("must not be negative, but get {}", min_area),
+        );
+    }
+    if let (Some(min_area), Some(max_area)) = (min_area, max_area) {
+        validator.check(
+            min_area <= max_area,
+            format!("{}.min_area", path),
+            format!(
+                "must not exceed max_area, but get {} > {}",
+                min_area, max_area
+            ),
+        );
+    }
+    if let Some(min_aspect_ratio) = min_aspect_ratio {
+        validator.check(
+            *min_aspect_ratio >= 1.0,
+            format!("{}.min_aspect_ratio", path),
+            format!(
+                "must be at least 1 since the ratio is of the longer side, but get {}",
+                min_aspect_ratio
+            ),
+        );
+    }
+    if let (Some(min_aspect_ratio), Some(max_aspect_ratio)) = (min_aspect_ratio, max_aspect_ratio) {
+        validator.check(
+            min_aspect_ratio <= max_aspect_ratio,
+            format!("{}.min_aspect_ratio", path),
+            format!(
+                "must not exceed max_aspect_ratio, but get {} > {}",
+                min_aspect_ratio, max_aspect_ratio
+            ),
+        );
+    }
+    for (name, ratio) in &[
+        ("min_rectangularity", min_rectangularity),
+        ("min_solidity", min_solidity),
+    ] {
+        if let Some(ratio) = ratio {
+            validator.check(
+                *ratio >= 0.0 && *ratio <= 1.0,
+                format!("{}.{}", path, name),
+                format!("must be in range [0, 1], but get {}", ratio),
+            );
+        }
+    }
+    if let (Some(min_mean_hsv), Some(max_mean_hsv)) = (min_mean_hsv, max_mean_hsv) {
+        for (index, (min, max)) in min_mean_hsv.iter().zip(max_mean_hsv).enumerate() {
+            validator.check(
+                min <= max,
+                format!("{}.min_mean_hsv[{}]", path, index),
+                format!("must not exceed max_mean_hsv, but get {} > {}", min, max),
+            );
+        }
+    }
+}
+
+// Checks the lower_bound and upper_bound fields under the path.
+fn validate_hsv_range(
+    validator: &mut Validator,
+    path: &str,
+    lower_bound: &[i32; 3],
+    upper_bound: &[i32; 3],
+) {
+    validate_hsv(validator, &format!("{}.lower_bound", path), lower_bound);
+    validate_hsv(validator, &format!("{}.upper_bound", path), upper_bound);
+    // hue wraps around if the lower bound exceeds the upper one, e.g. for reds
+    for (index, (lower, upper)) in lower_bound
+        .iter()
+        .zip(upper_bound.iter())
+        .enumerate()
+        .skip(1)
+    {
+        validator.check(
+            lower <= upper,
+            format!("{}.lower_bound[{}]", path, index),
+            format!("must not exceed upper_bound, but get {} > {}", lower, upper),
+        );
+    }
+}
+
+// The detector rounds even kernel sizes up to the next odd number.
+fn validate_kernel(validator: &mut Validator, path: &str, kernel: i32) {
+    if kernel <= 0 {
+        validator.report(path, format!("must be positive, but get {}", kernel));
+    } else {
+        validator.check_warn(
+            kernel % 2 == 1,
+            path,
+            format!("is rounded up to {} since it must be odd", kernel + 1),
+        );
+    }
+}
+
+// OpenCV represents hue in [0, 179], while saturation and value in [0, 255].
+// A hue above 179 is not an error since the range check simply never reaches it.
+fn validate_hsv(validator: &mut Validator, path: &str, hsv: &[i32; 3]) {
+    let [hue, saturation, value] = *hsv;
+    validator.check(
+        hue >= 0,
+        format!("{}[0]", path),
+        format!("hue must not be negative, but get {}", hue),
+    );
+    validator.check_warn(
+        hue <= 179,
+        format!("{}[0]", path),
+        format!("hue is at most 179 in OpenCV, but get {}", hue),
+    );
+    validator.check(
+        (0..=255).contains(&saturation),
+        format!("{}[1]", path),
+        format!(
+            "saturation must be in range 0..=255, but get {}",
+            saturation
+        ),
+    );
+    validator.check(
+        (0..=255).contains(&value),
+        format!("{}[2]", path),
+        format!("value must be in range 0..=255, but get {}", value),
+    );
+}
+
+fn to_i32(values: &[u8; 3]) -> [i32; 3] {
+    let [a, b, c] = *values;
+    [a as i32, b as i32, c as i32]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Map;
+    use std::path::Path;
+
+    const CONFIG: &str = r#"{
+        "dobot": { "enabled": false, "device": "/dev/null" },
+        "realsense": {
+            "depth_camera": { "width": 640, "height": 0, "fps": 30, "format": "Z16" },
+            "video_camera": { "width": 640, "height": 0, "fps": 30, "format": "Z16" },
+        },
+        "object_detector": {},
+        "visualizer": {
+            "enable_pcd_viewer": false,
+            "enable_video_viewer": false,
+            "enable_depth_viewer": false,
+            "enable_detection_viewer": false,
+        },
+        "controller": {
+            "linear_transform": [[1.0, 0.0], [0.0, 1.0]],
+            "translation": [0.0, 0.0],
+            "depth_image": [0.259, 0.249, 0.251],
+            "depth_robot": [-32.0, -25.0],
+        },
+    }"#;
+
+    #[test]
+    fn report_all_issues() {
+        let mut config: Config = json5::from_str(CONFIG).unwrap();
+        config.object_detector.detector.blur_kernel = Some(16);
+        config.object_detector.detector.min_arc_length = Some(200.0);
+        config.object_detector.detector.max_arc_length = Some(100.0);
+        config.object_detector.detector.upper_bound = Some([250, 255, 255]);
+        config.object_detector.detector.regions = Some(Regions {
+            include: vec![vec![[0.0, 0.0], [100.0, 0.0]]],
+            exclude: vec![],
+        });
+
+        let issues = config
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.severity, issue.path))
+            .collect::<Vec<_>>();
+        let expect = vec![
+            (Severity::Error, "realsense.video_camera.format"),
+            (Severity::Warning, "object_detector.blur_kernel"),
+            (Severity::Error, "object_detector.min_arc_length"),
+            (Severity::Warning, "object_detector.upper_bound[0]"),
+            (Severity::Error, "object_detector.regions.include[0]"),
+            (Severity::Error, "controller.depth_robot"),
+            (Severity::Error, "controller.depth_image[2]"),
+        ]
+        .into_iter()
+        .map(|(severity, path)| (severity, path.to_owned()))
+        .collect::<Vec<_>>();
+        assert_eq!(issues, expect);
+    }
+
+    #[test]
+    fn shipped_params_have_no_errors() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("params");
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let params = ObjectDetectorConfig::open(&path, &Map::new()).unwrap();
+            let errors = params
+                .validate()
+                .into_iter()
+                .filter(ConfigIssue::is_error)
+                .map(|issue| issue.to_string())
+                .collect::<Vec<_>>();
+            assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     config::{Config, VisualizerConfig},
-    message::{ControlMessage, VisualizerMessage},
+    frame_source::DepthImage,
+    message::{ControlMessage, RealSenseMessage, VisualizerMessage},
     state::GlobalState,
     utils::{HackyTryFrom, RateMeter, WatchedObject},
 };
@@ -11,14 +12,14 @@ use hacky_arm_common::opencv::{
     highgui, imgproc,
     prelude::*,
 };
-use image::{DynamicImage, GenericImageView, Rgba};
+use hacky_detection::draw_annotations;
+use image::{Rgb, RgbImage};
 use kiss3d::{
     light::Light,
     window::{State, Window},
 };
 use log::info;
-use nalgebra::{Point2, Point3, Rotation3, Vector3};
-use realsense_rust::{frame::marker as frame_marker, prelude::*, Frame};
+use nalgebra::{Point3, Rotation3, Vector3};
 use std::f32;
 use std::sync::Arc;
 use tokio::{runtime::Runtime, sync::broadcast, task::JoinHandle};
@@ -73,17 +74,19 @@ impl State for PcdVizState {
 }
 
 struct VisualizerCache {
-    color_frame: Option<Frame<frame_marker::Video>>,
-    depth_frame: Option<Frame<frame_marker::Depth>>,
+    color_image: Option<Arc<RgbImage>>,
+    depth_image: Option<Arc<DepthImage>>,
     image: Option<Mat>,
+    debug_images: Vec<(String, Mat)>,
 }
 
 impl VisualizerCache {
     pub fn new() -> Self {
         Self {
-            color_frame: None,
-            depth_frame: None,
+            color_image: None,
+            depth_image: None,
             image: None,
+            debug_images: vec![],
         }
     }
 }
@@ -164,21 +167,12 @@ impl Visualizer {
             };
 
             match msg {
-                VisualizerMessage::RealSenseData {
-                    depth_frame,
-                    color_frame,
-                    points,
-                    texture_coordinates,
-                } => {
-                    self.update_realsense_data(
-                        depth_frame.clone(),
-                        color_frame.clone(),
-                        Arc::clone(&points),
-                        texture_coordinates.clone(),
-                    )?;
+                VisualizerMessage::RealSenseData(realsense_msg) => {
+                    self.update_realsense_data(&realsense_msg)?;
                 }
                 VisualizerMessage::ObjectDetection(detection) => {
                     let mut image = Mat::from_slice_2d(&detection.image)?;
+                    draw_annotations(&mut image, &detection.annotations)?;
                     // info!("{:?}", detection.cloud_to_image_point_correspondences);
                     imgproc::put_text(
                         &mut image,
@@ -194,7 +188,17 @@ impl Visualizer {
                     for obj in detection.objects.iter() {
                         imgproc::put_text(
                             &mut image,
-                            &format!("({}, {})", obj.x, obj.y),
+                            &format!(
+                                "#{} {}{} ({}, {})",
+                                obj.track_id,
+                                obj.label,
+                                obj.shape
+                                    .as_ref()
+                                    .map(|shape| format!(" {}", shape))
+                                    .unwrap_or_default(),
+                                obj.x,
+                                obj.y
+                            ),
                             Point::new(obj.x + 30, obj.y - 30),
                             imgproc::FONT_HERSHEY_SIMPLEX,
                             0.5,
@@ -237,8 +241,29 @@ impl Visualizer {
                             imgproc::LINE_8,
                             false,
                         )?;
+                        if !obj.graspable {
+                            imgproc::put_text(
+                                &mut image,
+                                &format!(
+                                    "too wide: {:.1}(mm)",
+                                    obj.grasp_width.unwrap_or(0.0) * 1000.
+                                ),
+                                Point::new(obj.x + 30, obj.y + 50),
+                                imgproc::FONT_HERSHEY_SIMPLEX,
+                                0.5,
+                                Scalar::new(0., 0., 255., 0.),
+                                1,
+                                imgproc::LINE_8,
+                                false,
+                            )?;
+                        }
                     }
                     self.cache.image = Some(image);
+                    self.cache.debug_images = detection
+                        .debug_images
+                        .iter()
+                        .map(|(name, image)| Ok((name.clone(), Mat::from_slice_2d(image)?)))
+                        .collect::<Fallible<Vec<_>>>()?;
                 }
             }
 
@@ -255,26 +280,26 @@ impl Visualizer {
         Ok(())
     }
 
-    fn update_realsense_data(
-        &mut self,
-        depth_frame: Frame<frame_marker::Depth>,
-        color_frame: Frame<frame_marker::Video>,
-        points: Arc<Vec<Point3<f32>>>,
-        texture_coordinates: Vec<Point2<f32>>,
-    ) -> Fallible<()> {
-        let color_image: DynamicImage = color_frame.image()?.into();
+    fn update_realsense_data(&mut self, msg: &RealSenseMessage) -> Fallible<()> {
+        let RealSenseMessage {
+            color_image,
+            depth_image,
+            points,
+            texture_coordinates,
+            ..
+        } = msg;
         let (width, height) = color_image.dimensions();
 
         // construct points with color
         let colored_points = points
             .iter()
-            .zip(texture_coordinates.into_iter())
+            .zip(texture_coordinates.iter())
             .map(|(point, texture_coordinate)| {
                 let [x, y]: [_; 2] = texture_coordinate.coords.into();
                 let color = if x >= 0.0 && x < 1.0 && y >= 0.0 && y < 1.0 {
                     let row = (y * height as f32) as u32;
                     let col = (x * width as f32) as u32;
-                    let Rgba([r, g, b, _a]) = color_image.get_pixel(col, row);
+                    let Rgb([r, g, b]) = *color_image.get_pixel(col, row);
                     Point3::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
                 } else {
                     Point3::new(0.1, 0.1, 0.1)
@@ -289,8 +314,8 @@ impl Visualizer {
             let _ = tx.send(colored_points);
         }
 
-        self.cache.color_frame = Some(color_frame);
-        self.cache.depth_frame = Some(depth_frame);
+        self.cache.color_image = Some(Arc::clone(color_image));
+        self.cache.depth_image = Some(Arc::clone(depth_image));
         Ok(())
     }
 
@@ -303,17 +328,15 @@ impl Visualizer {
         } = self.config.visualizer;
 
         if enable_video_viewer && !is_dobot_busy {
-            if let Some(color_frame) = &self.cache.color_frame {
-                let color_image = color_frame.image()?;
-                let color_mat: Mat = HackyTryFrom::try_from(&color_image)?;
+            if let Some(color_image) = &self.cache.color_image {
+                let color_mat: Mat = HackyTryFrom::try_from(&**color_image)?;
                 highgui::imshow("Color", &color_mat)?;
             }
         }
 
         if enable_depth_viewer && !is_dobot_busy {
-            if let Some(depth_frame) = &self.cache.depth_frame {
-                let depth_image = depth_frame.image()?;
-                let depth_mat: Mat = HackyTryFrom::try_from(&depth_image)?;
+            if let Some(depth_image) = &self.cache.depth_image {
+                let depth_mat: Mat = HackyTryFrom::try_from(&depth_image.image)?;
                 let depth_mat = depth_mat
                     .mul(
                         &Mat::ones_size(depth_mat.size()?, depth_mat.typ()?)?.to_mat()?,
@@ -329,6 +352,9 @@ impl Visualizer {
                 highgui::named_window("Detection", 0)?;
                 highgui::imshow("Detection", image)?;
             }
+            for (name, image) in self.cache.debug_images.iter() {
+                highgui::imshow(name, image)?;
+            }
         }
 
         let key = highgui::wait_key(1)?;
@@ -360,6 +386,13 @@ impl Visualizer {
                     .send(ControlMessage::ToggleAutoGrab)
                     .unwrap();
             }
+            99 => {
+                // c
+                info!("Toggle recording.");
+                self.control_tx
+                    .send(ControlMessage::ToggleRecording)
+                    .unwrap();
+            }
             _ => (),
         }
 
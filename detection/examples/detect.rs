@@ -6,7 +6,7 @@ use hacky_arm_common::opencv::{
     prelude::*,
     videoio::{VideoCapture, CAP_V4L},
 };
-use hacky_detection::detector::Detector;
+use hacky_detection::{draw_annotations, DetectOptions, Detector};
 use std::os::unix::fs::FileTypeExt;
 
 #[derive(Debug, Clone, FromArgs)]
@@ -82,8 +82,13 @@ fn detect(mut image: Mat, detector: &Detector) -> Fallible<()> {
         imgproc::INTER_LINEAR,
     )?;
 
-    let objects = detector.detect(&mut image)?;
-    println!("\n\nResults: {:#?}", objects);
+    let options = DetectOptions {
+        annotations: true,
+        debug_images: false,
+    };
+    let result = detector.detect(&image, &options)?;
+    println!("\n\nResults: {:#?}", result.objects);
+    draw_annotations(&mut image, &result.annotations)?;
 
     // visualize the detection
     let window_name = "Detection";
@@ -0,0 +1,88 @@
+use argh::FromArgs;
+use failure::Fallible;
+use hacky_arm_common::opencv::{core, imgcodecs, prelude::*};
+use hacky_detection::{DetectorParams, ShapeTemplate};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, FromArgs)]
+/// Extracts the shape template of the largest object in a reference image
+/// and adds it to a parameter file.
+struct Args {
+    /// reference image file path, e.g. ./assets/1.png.
+    #[argh(option, short = 'f')]
+    pub file: PathBuf,
+    /// the parameter file whose detector finds the object, and where the
+    /// template is written to.
+    #[argh(option, short = 'p')]
+    pub params: PathBuf,
+    /// the shape label to add or update.
+    #[argh(option, short = 'l')]
+    pub label: String,
+}
+
+fn main() -> Fallible<()> {
+    let Args {
+        file,
+        params,
+        label,
+    } = argh::from_env();
+
+    let raw = imgcodecs::imread(
+        file.to_str()
+            .ok_or_else(|| failure::format_err!("invalid path {}", file.display()))?,
+        imgcodecs::IMREAD_COLOR,
+    )?;
+    if raw.empty()? {
+        failure::bail!("failed to read image {}", file.display());
+    }
+
+    let mut config: Value = if params.exists() {
+        DetectorParams::load_value(&params)?
+    } else {
+        json!({})
+    };
+    let detector = serde_json::from_value::<DetectorParams>(config.clone())?.to_detector();
+
+    // merge masks of all color classes
+    let normalized = detector.normalize(&raw)?;
+    let mut merged: Option<Mat> = None;
+    for (_, mask) in detector.color_masks(&normalized)? {
+        merged = Some(match merged {
+            Some(merged) => {
+                let mut union = Mat::default()?;
+                core::bitwise_or(&merged, &mask, &mut union, &core::no_array()?)?;
+                union
+            }
+            None => mask,
+        });
+    }
+    let merged = merged.ok_or_else(|| failure::format_err!("no color classes"))?;
+    let template = ShapeTemplate::from_mask(&label, &merged)?;
+    println!(
+        "extracted shape {:?} of {} points",
+        label,
+        template.contour.len()
+    );
+
+    // add or update the template by label
+    let shapes = config
+        .as_object_mut()
+        .ok_or_else(|| failure::format_err!("the parameter file is not a JSON object"))?
+        .entry("shapes")
+        .or_insert_with(|| json!({}));
+    let templates = shapes
+        .as_object_mut()
+        .ok_or_else(|| failure::format_err!("shapes is not an object"))?
+        .entry("templates")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or_else(|| failure::format_err!("shapes.templates is not an array"))?;
+    templates.retain(|template| template["label"].as_str() != Some(label.as_str()));
+    templates.push(serde_json::to_value(&template)?);
+
+    std::fs::write(&params, serde_json::to_string_pretty(&config)?)?;
+    println!("saved to {}", params.display());
+
+    Ok(())
+}
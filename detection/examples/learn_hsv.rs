@@ -0,0 +1,162 @@
+use argh::FromArgs;
+use failure::Fallible;
+use hacky_arm_common::opencv::{core::Rect, highgui, imgcodecs, prelude::*, types::VectorOfRect};
+use hacky_detection::{learn_hsv_range, sample_hsv, DetectorParams, HsvRangeOptions};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, FromArgs)]
+/// Learns the HSV range of selected regions and writes it to a parameter file.
+struct Args {
+    /// input image file path.
+    #[argh(option, short = 'f')]
+    pub file: PathBuf,
+    /// the parameter file to update, created if not existing.
+    #[argh(option, short = 'p')]
+    pub params: PathBuf,
+    /// the region "x,y,width,height" to sample, which can be repeated.
+    /// The regions are selected interactively if not given.
+    #[argh(option, short = 'r', from_str_fn(parse_rect))]
+    pub region: Vec<Rect>,
+    /// the class label to add or update, or the detector bounds if not given.
+    #[argh(option, short = 'l')]
+    pub label: Option<String>,
+    /// the margins "h,s,v" added to both ends of the range.
+    #[argh(option, from_str_fn(parse_margin))]
+    pub margin: Option<[i32; 3]>,
+    /// the percentage of outliers dropped at each end of every channel.
+    #[argh(option)]
+    pub outlier_percent: Option<f64>,
+}
+
+fn main() -> Fallible<()> {
+    let Args {
+        file,
+        params,
+        region,
+        label,
+        margin,
+        outlier_percent,
+    } = argh::from_env();
+
+    let image = imgcodecs::imread(
+        file.to_str()
+            .ok_or_else(|| failure::format_err!("invalid path {}", file.display()))?,
+        imgcodecs::IMREAD_COLOR,
+    )?;
+    if image.empty()? {
+        failure::bail!("failed to read image {}", file.display());
+    }
+
+    let rects = if region.is_empty() {
+        select_regions(&image)?
+    } else {
+        region
+    };
+    if rects.is_empty() {
+        failure::bail!("no regions are selected");
+    }
+
+    let options = {
+        let default = HsvRangeOptions::default();
+        HsvRangeOptions {
+            outlier_percent: outlier_percent.unwrap_or(default.outlier_percent),
+            margin: margin.unwrap_or(default.margin),
+        }
+    };
+    let samples = sample_hsv(&image, &rects)?;
+    let (lower_bound, upper_bound) = learn_hsv_range(&samples, &options)?;
+    println!(
+        "lower_bound: {:?}, upper_bound: {:?}",
+        lower_bound, upper_bound
+    );
+
+    let mut config: Value = if params.exists() {
+        DetectorParams::load_value(&params)?
+    } else {
+        json!({})
+    };
+    update_params(&mut config, label, lower_bound, upper_bound)?;
+    std::fs::write(&params, serde_json::to_string_pretty(&config)?)?;
+    println!("saved to {}", params.display());
+
+    Ok(())
+}
+
+/// Lets the user drag rectangles on the image, confirming each with space or
+/// enter, and finishing with escape.
+fn select_regions(image: &Mat) -> Fallible<Vec<Rect>> {
+    let window = "select regions";
+    let mut rects = VectorOfRect::new();
+    highgui::select_rois(window, image, &mut rects, true, false)?;
+    highgui::destroy_window(window)?;
+    Ok(rects
+        .to_vec()
+        .into_iter()
+        .filter(|rect| rect.width > 0 && rect.height > 0)
+        .collect())
+}
+
+/// Writes the bounds into the class of the label, or the detector bounds if
+/// no label is given, keeping other fields as is.
+fn update_params(
+    config: &mut Value,
+    label: Option<String>,
+    lower_bound: [i32; 3],
+    upper_bound: [i32; 3],
+) -> Fallible<()> {
+    let config = config
+        .as_object_mut()
+        .ok_or_else(|| failure::format_err!("the parameter file is not a JSON object"))?;
+
+    let label = match label {
+        Some(label) => label,
+        None => {
+            config.insert("lower_bound".into(), json!(lower_bound));
+            config.insert("upper_bound".into(), json!(upper_bound));
+            return Ok(());
+        }
+    };
+
+    let classes = config
+        .entry("classes")
+        .or_insert_with(|| json!([]))
+        .as_array_mut()
+        .ok_or_else(|| failure::format_err!("classes is not an array"))?;
+    let class = classes
+        .iter_mut()
+        .find(|class| class["label"].as_str() == Some(label.as_str()));
+    match class {
+        Some(class) => {
+            class["lower_bound"] = json!(lower_bound);
+            class["upper_bound"] = json!(upper_bound);
+        }
+        None => classes.push(json!({
+            "label": label,
+            "lower_bound": lower_bound,
+            "upper_bound": upper_bound,
+        })),
+    }
+    Ok(())
+}
+
+fn parse_rect(value: &str) -> Result<Rect, String> {
+    match parse_numbers(value)?.as_slice() {
+        &[x, y, width, height] => Ok(Rect::new(x, y, width, height)),
+        _ => Err(format!("expect x,y,width,height but get {}", value)),
+    }
+}
+
+fn parse_margin(value: &str) -> Result<[i32; 3], String> {
+    match parse_numbers(value)?.as_slice() {
+        &[h, s, v] => Ok([h, s, v]),
+        _ => Err(format!("expect h,s,v but get {}", value)),
+    }
+}
+
+fn parse_numbers(value: &str) -> Result<Vec<i32>, String> {
+    value
+        .split(',')
+        .map(|number| number.trim().parse().map_err(|err| format!("{}", err)))
+        .collect()
+}
@@ -1,11 +1,11 @@
 use argh::FromArgs;
 use failure::Fallible;
 use hacky_arm_common::opencv::{
-    core::{self, Point2f, RotatedRect, Scalar, Size},
+    core::{self, Point, Point2f, RotatedRect, Scalar, Size},
     highgui, imgcodecs, imgproc,
     prelude::*,
 };
-use hacky_detection::detector::Detector;
+use hacky_detection::{draw_annotations, DetectOptions, Detector};
 use log::info;
 use serde::Serialize;
 use std::fs::File;
@@ -21,13 +21,15 @@ struct Args {
 
 #[derive(Serialize)]
 struct Tunable {
-    threshold: i32,
     n_dilations: i32,
     n_erosions: i32,
     // kernel_size: i32,
     n_objects: i32,
     min_arc_length: i32,
     max_arc_length: i32,
+    /// the image to show, the annotated result if zero, or the debug image otherwise
+    #[serde(skip)]
+    stage: i32,
 }
 
 fn main() -> Fallible<()> {
@@ -46,16 +48,15 @@ fn main() -> Fallible<()> {
     let mut raw: Mat = imgcodecs::imread(&file, imgcodecs::IMREAD_COLOR)?;
 
     let mut tunable = Tunable {
-        threshold: detector.threshold as i32,
         n_dilations: detector.n_dilations,
         n_erosions: detector.n_erosions,
         // kernel_size: detector.kernel_size,
         n_objects: detector.n_objects as i32,
         min_arc_length: detector.min_arc_length as i32,
         max_arc_length: detector.max_arc_length as i32,
+        stage: 0,
     };
 
-    highgui::create_trackbar("threshold", window_name, &mut tunable.threshold, 255, None)?;
     highgui::create_trackbar(
         "n_dilations",
         window_name,
@@ -94,12 +95,47 @@ fn main() -> Fallible<()> {
         imgproc::INTER_LINEAR,
     )?;
 
+    let options = DetectOptions {
+        annotations: true,
+        debug_images: true,
+    };
+    let n_debug_images = detector.detect(&raw, &options)?.debug_images.len();
+    highgui::create_trackbar(
+        "stage",
+        window_name,
+        &mut tunable.stage,
+        n_debug_images as i32,
+        None,
+    )?;
+
     // visualize the detection
     loop {
-        let mut raw_img = raw.clone()?;
-        let objects = detector.detect(&mut raw_img)?;
-        highgui::imshow(window_name, &raw_img)?;
-        info!("\n\nResults: {:#?}", objects);
+        let result = detector.detect(&raw, &options)?;
+        let image = match (tunable.stage as usize).checked_sub(1) {
+            Some(index) if index < result.debug_images.len() => {
+                let (name, image) = &result.debug_images[index];
+                let mut image = image.clone()?;
+                imgproc::put_text(
+                    &mut image,
+                    name,
+                    Point::new(5, 25),
+                    imgproc::FONT_HERSHEY_SIMPLEX,
+                    0.7,
+                    Scalar::all(255.),
+                    2,
+                    imgproc::LINE_8,
+                    false,
+                )?;
+                image
+            }
+            _ => {
+                let mut image = raw.clone()?;
+                draw_annotations(&mut image, &result.annotations)?;
+                image
+            }
+        };
+        highgui::imshow(window_name, &image)?;
+        info!("\n\nResults: {:#?}", result.objects);
         let key = highgui::wait_key(10)?;
         if key == 113 {
             break;
@@ -109,7 +145,6 @@ fn main() -> Fallible<()> {
             write!(&mut file, "{}", config)?;
             println!("\n\n Config saved!");
         } else {
-            detector.threshold = tunable.threshold as f64;
             detector.n_dilations = tunable.n_dilations;
             detector.n_erosions = tunable.n_erosions;
             // detector.kernel_size = tunable.kernel_size;
@@ -0,0 +1,89 @@
+use failure::Fallible;
+use hacky_arm_common::opencv::{
+    core::{Point, Scalar},
+    imgproc,
+    prelude::*,
+};
+
+/// A drawing primitive that annotates detection results on an image.
+///
+/// Colors are in BGR order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation {
+    Polyline {
+        points: Vec<(i32, i32)>,
+        closed: bool,
+        color: [f64; 3],
+        thickness: i32,
+    },
+    Text {
+        text: String,
+        origin: (i32, i32),
+        scale: f64,
+        color: [f64; 3],
+    },
+}
+
+impl Annotation {
+    /// Draws the annotation on the image.
+    pub fn draw(&self, img: &mut Mat) -> Fallible<()> {
+        match self {
+            Annotation::Polyline {
+                points,
+                closed,
+                color,
+                thickness,
+            } => {
+                let n_lines = if *closed {
+                    points.len()
+                } else {
+                    points.len().saturating_sub(1)
+                };
+                for index in 0..n_lines {
+                    let (x1, y1) = points[index];
+                    let (x2, y2) = points[(index + 1) % points.len()];
+                    imgproc::line(
+                        img,
+                        Point::new(x1, y1),
+                        Point::new(x2, y2),
+                        to_scalar(color),
+                        *thickness,
+                        imgproc::LINE_8,
+                        0,
+                    )?;
+                }
+            }
+            Annotation::Text {
+                text,
+                origin: (x, y),
+                scale,
+                color,
+            } => {
+                imgproc::put_text(
+                    img,
+                    text,
+                    Point::new(*x, *y),
+                    imgproc::FONT_HERSHEY_SIMPLEX,
+                    *scale,
+                    to_scalar(color),
+                    1,
+                    imgproc::LINE_8,
+                    false,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Draws all annotations on the image in order.
+pub fn draw_annotations(img: &mut Mat, annotations: &[Annotation]) -> Fallible<()> {
+    for annotation in annotations.iter() {
+        annotation.draw(img)?;
+    }
+    Ok(())
+}
+
+fn to_scalar(color: &[f64; 3]) -> Scalar {
+    Scalar::new(color[0], color[1], color[2], 0.)
+}
@@ -0,0 +1,154 @@
+use argh::FromArgs;
+use failure::Fallible;
+use hacky_detection::{
+    evaluate_detector, search, Candidate, Dataset, DetectorParams, EvaluationOptions, Param,
+    SearchSpace,
+};
+use log::info;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{fs::File, io::BufReader, path::PathBuf};
+
+#[derive(Debug, Clone, FromArgs)]
+/// Searches detector parameters that perform best over a labeled dataset.
+struct Args {
+    /// the dataset file listing images and ground truth objects.
+    #[argh(option, short = 'd')]
+    pub dataset: PathBuf,
+    /// the search space file of the method and parameter ranges.
+    #[argh(option, short = 's')]
+    pub space: PathBuf,
+    /// the parameter file to start from, the defaults if not given.
+    #[argh(option, short = 'p')]
+    pub params: Option<PathBuf>,
+    /// the parameter file to write the best candidate to.
+    #[argh(option, short = 'o')]
+    pub output: PathBuf,
+    /// the file to write the JSON report of best candidates to.
+    #[argh(option, short = 'r')]
+    pub report: Option<PathBuf>,
+    /// the number of best candidates to report.
+    #[argh(option, default = "5")]
+    pub top: usize,
+    /// the maximum distance in pixels to match a detection with a ground truth.
+    #[argh(option)]
+    pub max_center_distance: Option<f32>,
+}
+
+/// The summary of a candidate in the report.
+#[derive(Debug, Serialize)]
+struct CandidateSummary {
+    rank: usize,
+    values: Vec<(Param, f64)>,
+    precision: f64,
+    recall: f64,
+    f1_score: f64,
+    mean_center_error: Option<f32>,
+    mean_angle_error: Option<f32>,
+}
+
+fn main() -> Fallible<()> {
+    pretty_env_logger::init();
+    let Args {
+        dataset,
+        space,
+        params,
+        output,
+        report,
+        top,
+        max_center_distance,
+    } = argh::from_env();
+
+    let images = Dataset::load(&dataset)?.read_images()?;
+    let space: SearchSpace = serde_json::from_reader(BufReader::new(File::open(&space)?))?;
+    // keep fields unknown to the detector when writing the result
+    let base_value: Value = match &params {
+        Some(path) => DetectorParams::load_value(path)?,
+        None => json!({}),
+    };
+    let base: DetectorParams = serde_json::from_value(base_value.clone())?;
+    let options = {
+        let mut options = EvaluationOptions::default();
+        if let Some(max_center_distance) = max_center_distance {
+            options.max_center_distance = max_center_distance;
+        }
+        options
+    };
+
+    let mut n_evaluations = 0;
+    let candidates = search(&base, &space, |params| {
+        let report = evaluate_detector(&params.to_detector(), &images, &options)?;
+        n_evaluations += 1;
+        info!(
+            "candidate {}: f1 {:.3}, precision {:.3}, recall {:.3}",
+            n_evaluations,
+            report.f1_score(),
+            report.precision,
+            report.recall
+        );
+        Ok(report)
+    })?;
+
+    let summaries = candidates
+        .iter()
+        .take(top)
+        .enumerate()
+        .map(|(index, candidate)| summarize(index + 1, candidate))
+        .collect::<Vec<_>>();
+    println!("evaluated {} candidates", candidates.len());
+    for summary in summaries.iter() {
+        let values = summary
+            .values
+            .iter()
+            .map(|(param, value)| format!("{:?}={}", param, value))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "#{}: f1 {:.3}, precision {:.3}, recall {:.3}, center error {:?}, angle error {:?}, {}",
+            summary.rank,
+            summary.f1_score,
+            summary.precision,
+            summary.recall,
+            summary.mean_center_error,
+            summary.mean_angle_error,
+            values
+        );
+    }
+
+    // write the best parameters over the base file
+    let best = match candidates.first() {
+        Some(best) => best,
+        None => failure::bail!("no candidates are evaluated"),
+    };
+    let mut params_value = base_value;
+    if let (Some(params_value), Value::Object(best_value)) = (
+        params_value.as_object_mut(),
+        serde_json::to_value(&best.params)?,
+    ) {
+        params_value.extend(best_value);
+    } else {
+        failure::bail!("the parameter file is not a JSON object");
+    }
+    std::fs::write(&output, serde_json::to_string_pretty(&params_value)?)?;
+    println!("saved the best parameters to {}", output.display());
+
+    if let Some(report) = report {
+        std::fs::write(&report, serde_json::to_string_pretty(&summaries)?)?;
+        println!("saved the report to {}", report.display());
+    }
+
+    Ok(())
+}
+
+fn summarize(rank: usize, candidate: &Candidate) -> CandidateSummary {
+    let Candidate { values, report, .. } = candidate;
+    CandidateSummary {
+        rank,
+        values: values.clone(),
+        precision: report.precision,
+        recall: report.recall,
+        f1_score: report.f1_score(),
+        mean_center_error: report.mean_center_error,
+        mean_angle_error: report.mean_angle_error,
+    }
+}
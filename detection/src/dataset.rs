@@ -0,0 +1,98 @@
+//! The ground truth format of labeled images.
+//!
+//! A dataset is a JSON file listing images and the objects in them, e.g.
+//!
+//! ```json
+//! {
+//!     "images": [
+//!         {
+//!             "file": "pic/pen-cap-1.jpg",
+//!             "objects": [
+//!                 {
+//!                     "label": "object",
+//!                     "center": [320.0, 240.0],
+//!                     "angle": 30.0,
+//!                     "polygon": [[300.0, 200.0], [350.0, 220.0], [340.0, 280.0], [290.0, 260.0]]
+//!                 }
+//!             ]
+//!         }
+//!     ]
+//! }
+//! ```
+//!
+//! Image paths are relative to the directory of the dataset file. Angles are
+//! in degrees in the convention of the detector. The label, angle and
+//! polygon of an object are optional.
+
+use failure::Fallible;
+use hacky_arm_common::opencv::{imgcodecs, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+/// A set of labeled images.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Dataset {
+    pub images: Vec<LabeledImage>,
+}
+
+/// An image and its ground truth objects.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabeledImage {
+    pub file: PathBuf,
+    #[serde(default)]
+    pub objects: Vec<LabeledObject>,
+}
+
+/// A ground truth object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LabeledObject {
+    /// the label detections must have, any label if not given
+    #[serde(default)]
+    pub label: Option<String>,
+    /// the center [x, y] in pixels
+    pub center: [f32; 2],
+    /// the angle in degrees, which is not checked if not given, e.g. for round objects
+    #[serde(default)]
+    pub angle: Option<f32>,
+    /// the outline of vertices [x, y] in pixels
+    #[serde(default)]
+    pub polygon: Vec<[f32; 2]>,
+}
+
+impl Dataset {
+    /// Loads the dataset file, and resolves image paths against its directory.
+    pub fn load<P>(path: P) -> Fallible<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let mut dataset: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for image in dataset.images.iter_mut() {
+            image.file = dir.join(&image.file);
+        }
+        Ok(dataset)
+    }
+
+    /// Reads the BGR images along with their labels.
+    pub fn read_images(&self) -> Fallible<Vec<(LabeledImage, Mat)>> {
+        self.images
+            .iter()
+            .map(|image| {
+                let path = image
+                    .file
+                    .to_str()
+                    .ok_or_else(|| failure::format_err!("invalid path {}", image.file.display()))?;
+                let raw = imgcodecs::imread(path, imgcodecs::IMREAD_COLOR)?;
+                if raw.empty()? {
+                    failure::bail!("failed to read image {}", image.file.display());
+                }
+                Ok((image.clone(), raw))
+            })
+            .collect()
+    }
+}
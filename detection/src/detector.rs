@@ -1,20 +1,77 @@
+use crate::{
+    annotation::Annotation,
+    measurement::{Measurements, ObjectFilter},
+    normalization::{self, Normalization},
+    pipeline::{KernelShape, Morphology, Stage},
+    region::Regions,
+    shape::ShapeClassifier,
+    split::Split,
+};
 use failure::Fallible;
 use geo::{Coordinate, LineString};
 use hacky_arm_common::opencv::{
-    core::{self, Point, Point2f, RotatedRect, Scalar, Size},
+    core::{Point, Point2f, RotatedRect, Size},
     imgproc,
     prelude::*,
-    types::{VectorOfVectorOfPoint, VectorOfi32},
+    types::VectorOfVectorOfPoint,
 };
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone)]
 pub struct Obj {
+    /// the label of the color class the object belongs to
+    pub label: String,
     pub x: i32,
     pub y: i32,
     pub angle: f32,
     pub polygon: LineString<f32>,
+    pub measurements: Measurements,
+    /// the label of the closest shape template, `None` if unknown or not classified
+    pub shape: Option<String>,
+    /// the distance to the closest shape template, where lower is closer
+    pub shape_score: Option<f64>,
+}
+
+/// A named HSV range to detect objects of one kind.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorClass {
+    pub label: String,
+    pub lower_bound: [i32; 3],
+    pub upper_bound: [i32; 3],
+    /// overrides the inversion of the detector
+    #[serde(default)]
+    pub inversion: Option<bool>,
+    /// overrides the pipeline of the detector
+    #[serde(default)]
+    pub pipeline: Option<Vec<Stage>>,
 }
 
+/// What to collect along with detected objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DetectOptions {
+    /// draw primitives of object rectangles and positions
+    pub annotations: bool,
+    /// intermediate images of every stage and mask
+    pub debug_images: bool,
+}
+
+/// The result of detection, which leaves the input image untouched.
+#[derive(Debug)]
+pub struct DetectionResult {
+    pub objects: Vec<Obj>,
+    /// empty unless requested in options
+    pub annotations: Vec<Annotation>,
+    /// named intermediate images, empty unless requested in options
+    ///
+    /// Stage outputs are named "<label>/<index>:<stage>", e.g. "brick/1:median_blur",
+    /// the masks objects are found on are named "<label>/mask", and the image
+    /// after lighting normalization is named "normalized".
+    pub debug_images: Vec<(String, Mat)>,
+}
+
+/// The label of objects if no color classes are given.
+pub const DEFAULT_LABEL: &str = "object";
+
 #[derive(Debug)]
 pub struct Detector {
     pub inversion: bool,
@@ -29,6 +86,20 @@ pub struct Detector {
     pub roi: [f64; 2],
     pub lower_bound: [i32; 3],
     pub upper_bound: [i32; 3],
+    /// the lighting normalization steps applied before the stages of all classes
+    pub normalization: Vec<Normalization>,
+    /// the image processing stages, built from the fields above if not set
+    pub pipeline: Option<Vec<Stage>>,
+    /// the color classes, which replace the bounds above if not empty
+    pub classes: Vec<ColorClass>,
+    /// the thresholds on object measurements
+    pub filter: ObjectFilter,
+    /// the polygons where objects are detected, checked along with roi
+    pub regions: Regions,
+    /// separates touching objects in masks if set
+    pub split: Option<Split>,
+    /// classifies object shapes against templates if set
+    pub shapes: Option<ShapeClassifier>,
     pub draw_position: bool,
 }
 
@@ -47,93 +118,203 @@ impl Default for Detector {
             roi: [0.8, 0.8],
             lower_bound: [0, 57, 95],
             upper_bound: [26, 158, 255],
+            normalization: vec![],
+            pipeline: None,
+            classes: vec![],
+            filter: ObjectFilter::default(),
+            regions: Regions::default(),
+            split: None,
+            shapes: None,
             draw_position: true,
         }
     }
 }
 
 impl Detector {
-    pub fn detect(&self, raw: &mut Mat) -> Fallible<Vec<Obj>> {
-        let to_odd = |value: i32| value.max(3) | 1;
+    /// The stages equivalent to the fixed pipeline of earlier versions.
+    pub fn default_pipeline(&self) -> Vec<Stage> {
+        self.default_stages(self.inversion)
+    }
+
+    fn default_stages(&self, inversion: bool) -> Vec<Stage> {
+        let mut stages = vec![
+            Stage::HsvThreshold {
+                lower_bound: None,
+                upper_bound: None,
+            },
+            Stage::MedianBlur {
+                kernel: self.blur_kernel,
+            },
+        ];
+        if inversion {
+            stages.push(Stage::Invert);
+        }
+        stages.push(Stage::Dilate(Morphology {
+            shape: KernelShape::Cross,
+            kernel: self.dilation_kernel,
+            iterations: self.n_dilations,
+        }));
+        stages.push(Stage::Erode(Morphology {
+            shape: KernelShape::Cross,
+            kernel: self.erosion_kernel,
+            iterations: self.n_erosions,
+        }));
+        stages
+    }
+
+    /// The color classes to detect, falling back to a single class from the detector bounds.
+    pub fn color_classes(&self) -> Vec<ColorClass> {
+        if !self.classes.is_empty() {
+            return self.classes.clone();
+        }
+        vec![ColorClass {
+            label: DEFAULT_LABEL.to_owned(),
+            lower_bound: self.lower_bound,
+            upper_bound: self.upper_bound,
+            inversion: None,
+            pipeline: None,
+        }]
+    }
 
-        // start of image processing
-        let mut img = Mat::default()?;
+    /// Applies the lighting normalization steps on the BGR image.
+    pub fn normalize(&self, raw: &Mat) -> Fallible<Mat> {
+        normalization::normalize(raw, &self.normalization)
+    }
 
-        // - HSV threshold
-        imgproc::cvt_color(raw, &mut img, imgproc::COLOR_BGR2HSV, 0)?;
-        let lower_bound = VectorOfi32::from_iter(self.lower_bound.iter().map(ToOwned::to_owned));
-        let upper_bound = VectorOfi32::from_iter(self.upper_bound.iter().map(ToOwned::to_owned));
-        core::in_range(&img.clone()?, &lower_bound, &upper_bound, &mut img)?;
+    /// Runs the image processing stages for the color class and returns the binary mask.
+    ///
+    /// The image is expected to be normalized by [`normalize`](Detector::normalize).
+    pub fn mask(&self, raw: &Mat, class: &ColorClass) -> Fallible<Mat> {
+        self.run_stages(raw, class, None)
+    }
 
-        // - blurring
-        imgproc::median_blur(&img.clone()?, &mut img, to_odd(self.blur_kernel))?;
+    // Runs the stages for the color class, and keeps the output of each
+    // stage if debug images are given.
+    fn run_stages(
+        &self,
+        raw: &Mat,
+        class: &ColorClass,
+        mut debug_images: Option<&mut Vec<(String, Mat)>>,
+    ) -> Fallible<Mat> {
+        let mut img = raw.clone()?;
+        let bounds = (class.lower_bound, class.upper_bound);
+        let default_stages;
+        let stages = match (&class.pipeline, &self.pipeline) {
+            (Some(stages), _) | (None, Some(stages)) => stages,
+            (None, None) => {
+                default_stages = self.default_stages(class.inversion.unwrap_or(self.inversion));
+                &default_stages
+            }
+        };
+        for (index, stage) in stages.iter().enumerate() {
+            stage.apply(&mut img, &bounds)?;
+            if let Some(debug_images) = debug_images.as_mut() {
+                let name = format!("{}/{}:{}", class.label, index, stage.name());
+                debug_images.push((name, img.clone()?));
+            }
+        }
+        if img.channels()? != 1 {
+            failure::bail!("the detection pipeline must produce a binary mask");
+        }
+        Ok(img)
+    }
 
-        // - inversion
-        if self.inversion {
-            core::bitwise_not(&img.clone()?, &mut img, &core::no_array()?)?;
+    /// Computes the masks of color classes on the normalized image.
+    pub fn color_masks(&self, raw: &Mat) -> Fallible<Vec<(String, Mat)>> {
+        self.color_classes()
+            .into_iter()
+            .map(|class| {
+                let mask = self.mask(raw, &class)?;
+                Ok((class.label, mask))
+            })
+            .collect()
+    }
+
+    /// Detects objects of all color classes in the BGR image, which is
+    /// normalized first.
+    pub fn detect(&self, raw: &Mat, options: &DetectOptions) -> Fallible<DetectionResult> {
+        let normalized = self.normalize(raw)?;
+        let raw = &normalized;
+
+        let mut stage_images = vec![];
+        if options.debug_images && !self.normalization.is_empty() {
+            stage_images.push(("normalized".to_owned(), normalized.clone()?));
         }
+        let masks = self
+            .color_classes()
+            .into_iter()
+            .map(|class| {
+                let debug_images = if options.debug_images {
+                    Some(&mut stage_images)
+                } else {
+                    None
+                };
+                let mask = self.run_stages(raw, &class, debug_images)?;
+                Ok((class.label, mask))
+            })
+            .collect::<Fallible<Vec<_>>>()?;
 
-        // - dilation
-        let dilation_kernel: Mat = imgproc::get_structuring_element(
-            imgproc::MORPH_CROSS,
-            Size {
-                width: to_odd(self.dilation_kernel),
-                height: to_odd(self.dilation_kernel),
-            },
-            Point::new(-1, -1),
-        )?;
-        imgproc::dilate(
-            &img.clone()?,
-            &mut img,
-            &dilation_kernel,
-            Point::new(-1, -1),
-            self.n_dilations,
-            core::BORDER_CONSTANT,
-            imgproc::morphology_default_border_value()?,
-        )?;
-
-        // - erosion
-        let erosion_kernel: Mat = imgproc::get_structuring_element(
-            imgproc::MORPH_CROSS,
-            Size {
-                width: to_odd(self.erosion_kernel),
-                height: to_odd(self.erosion_kernel),
-            },
-            Point::new(-1, -1),
-        )?;
-        imgproc::erode(
-            &Mat::clone(&img)?,
-            &mut img,
-            &erosion_kernel,
-            Point::new(-1, -1),
-            self.n_erosions,
-            core::BORDER_CONSTANT,
-            imgproc::morphology_default_border_value()?,
-        )?;
-        // end of image processing
-
-        // find contours
+        let mut result = self.detect_masks(raw, &masks, options)?;
+        stage_images.append(&mut result.debug_images);
+        result.debug_images = stage_images;
+        Ok(result)
+    }
+
+    /// Finds objects from labeled binary masks, which may come from color
+    /// classes or other segmentation methods.
+    pub fn detect_masks(
+        &self,
+        raw: &Mat,
+        masks: &[(String, Mat)],
+        options: &DetectOptions,
+    ) -> Fallible<DetectionResult> {
+        let mut annotations = vec![];
+        let mut debug_images = vec![];
+        if options.debug_images {
+            for (label, mask) in masks.iter() {
+                debug_images.push((format!("{}/mask", label), mask.clone()?));
+            }
+        }
+
+        // measure colors in HSV
+        let mut hsv = Mat::default()?;
+        imgproc::cvt_color(raw, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+
+        // find contours of all masks
         let contours = {
-            let mut contours = VectorOfVectorOfPoint::new();
-            imgproc::find_contours(
-                &img,
-                &mut contours,
-                imgproc::RETR_EXTERNAL,
-                imgproc::CHAIN_APPROX_SIMPLE,
-                Point::default(),
-            )?;
+            let mut labeled_contours = vec![];
+            for (label, img) in masks.iter() {
+                let contours = match &self.split {
+                    Some(split) => split.contours(raw, img)?,
+                    None => {
+                        let mut contours = VectorOfVectorOfPoint::new();
+                        imgproc::find_contours(
+                            img,
+                            &mut contours,
+                            imgproc::RETR_EXTERNAL,
+                            imgproc::CHAIN_APPROX_SIMPLE,
+                            Point::default(),
+                        )?;
+                        contours.to_vec()
+                    }
+                };
+                labeled_contours.extend(contours.into_iter().map(|cnt| (label.clone(), cnt)));
+            }
 
-            let mut sorted_contours = contours.to_vec();
-            sorted_contours.sort_by_cached_key(|cnt| {
+            labeled_contours.sort_by_cached_key(|(_, cnt)| {
                 (-1000.0 * imgproc::arc_length(&cnt, true).unwrap()) as i32
             });
-            sorted_contours
+            labeled_contours
         };
 
-        let mut rotated_rects = vec![];
         let mut objects = vec![];
 
-        for cnt in contours.iter().take(self.n_objects) {
+        for (label, cnt) in contours.iter() {
+            // count accepted objects only, so rejected contours do not take up slots
+            if objects.len() >= self.n_objects {
+                break;
+            }
+
             let polygon: LineString<_> = cnt
                 .iter()
                 .map(|point| {
@@ -164,19 +345,37 @@ impl Detector {
                 let center_y = height / 2;
                 let shift_x = (width as f64 * self.roi[0] / 2.) as i32;
                 let shift_y = (height as f64 * self.roi[1] / 2.) as i32;
-                let roi_point_1 = (center_x - shift_x, center_y - shift_y);
-                let roi_point_2 = (center_x + shift_x, center_y + shift_y);
-
-                let _point = {
-                    let Point { x, y } = point.clone();
-                    (x, y)
-                };
+                let Point { x, y } = point;
 
-                if _point < roi_point_1 || _point > roi_point_2 {
+                // the center must be within the box on both axes
+                if (x - center_x).abs() > shift_x || (y - center_y).abs() > shift_y {
                     continue;
                 }
             }
 
+            // reject objects out of include regions or in exclude regions
+            if !self.regions.contains(point.x as f32, point.y as f32) {
+                continue;
+            }
+
+            // reject objects by measurements
+            let measurements = Measurements::new(cnt, &rotated_rect, &hsv)?;
+            if !self.filter.accepts(&measurements) {
+                continue;
+            }
+
+            // classify the shape and reject unknown ones if requested
+            let (shape, shape_score) = match &self.shapes {
+                Some(classifier) => {
+                    let (shape, shape_score) = classifier.classify(cnt)?;
+                    if shape.is_none() && classifier.reject_unknown {
+                        continue;
+                    }
+                    (shape, shape_score)
+                }
+                None => (None, None),
+            };
+
             // compute rotation angle
             let mut points = vec![Point2f::new(0., 0.); 4];
             rotated_rect.points(points.as_mut())?;
@@ -195,62 +394,143 @@ impl Detector {
                 -angle - 90.0
             };
 
-            // display rectangle
-            for index in 0..4 {
-                let next_index = (index + 1) % 4;
-                imgproc::line(
-                    raw,
-                    points[index].to::<i32>().unwrap(),
-                    points[next_index].to::<i32>().unwrap(),
-                    Scalar::new(0., 255., 0., 0.),
-                    3,
-                    imgproc::LINE_8,
-                    0,
-                )?;
+            // annotate rectangle
+            if options.annotations {
+                let points = points
+                    .iter()
+                    .map(|point| {
+                        let Point { x, y } = point.to::<i32>().unwrap();
+                        (x, y)
+                    })
+                    .collect();
+                annotations.push(Annotation::Polyline {
+                    points,
+                    closed: true,
+                    color: [0., 255., 0.],
+                    thickness: 3,
+                });
             }
 
             let obj = {
                 let Point { x, y } = point;
                 Obj {
+                    label: label.clone(),
                     x,
                     y,
                     angle,
                     polygon,
+                    measurements,
+                    shape,
+                    shape_score,
                 }
             };
 
-            rotated_rects.push(rotated_rect);
             objects.push(obj);
         }
 
-        // show objects info
-        if self.draw_position {
+        // annotate regions
+        if options.annotations {
+            let to_points = |vertices: &Vec<[f32; 2]>| {
+                vertices
+                    .iter()
+                    .map(|&[x, y]| (x.round() as i32, y.round() as i32))
+                    .collect()
+            };
+            for vertices in self.regions.include.iter() {
+                annotations.push(Annotation::Polyline {
+                    points: to_points(vertices),
+                    closed: true,
+                    color: [255., 255., 0.],
+                    thickness: 1,
+                });
+            }
+            for vertices in self.regions.exclude.iter() {
+                annotations.push(Annotation::Polyline {
+                    points: to_points(vertices),
+                    closed: true,
+                    color: [0., 0., 255.],
+                    thickness: 1,
+                });
+            }
+        }
+
+        // annotate objects info
+        if options.annotations && self.draw_position {
             for obj in objects.iter() {
-                imgproc::put_text(
-                    raw,
-                    &format!("({}, {})", obj.x, obj.y),
-                    Point::new(obj.x + 20, obj.y - 10),
-                    imgproc::FONT_HERSHEY_SIMPLEX,
-                    0.5,
-                    Scalar::new(0., 0., 255., 0.),
-                    1,
-                    imgproc::LINE_8,
-                    false,
-                )?;
-                imgproc::put_text(
-                    raw,
-                    &format!("angle: {:.2}(deg)", obj.angle),
-                    Point::new(obj.x + 20, obj.y + 15),
-                    imgproc::FONT_HERSHEY_SIMPLEX,
-                    0.5,
-                    Scalar::new(0., 0., 255., 0.),
-                    1,
-                    imgproc::LINE_8,
-                    false,
-                )?;
+                annotations.push(Annotation::Text {
+                    text: format!("({}, {})", obj.x, obj.y),
+                    origin: (obj.x + 20, obj.y - 10),
+                    scale: 0.5,
+                    color: [0., 0., 255.],
+                });
+                annotations.push(Annotation::Text {
+                    text: format!("angle: {:.2}(deg)", obj.angle),
+                    origin: (obj.x + 20, obj.y + 15),
+                    scale: 0.5,
+                    color: [0., 0., 255.],
+                });
+                if let (Some(shape), Some(shape_score)) = (&obj.shape, obj.shape_score) {
+                    annotations.push(Annotation::Text {
+                        text: format!("shape: {} ({:.3})", shape, shape_score),
+                        origin: (obj.x + 20, obj.y + 40),
+                        scale: 0.5,
+                        color: [0., 0., 255.],
+                    });
+                }
             }
         }
 
-        Ok(objects)
+        Ok(DetectionResult {
+            objects,
+            annotations,
+            debug_images,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hacky_arm_common::opencv::core::{self, Rect};
+
+    #[test]
+    fn rejected_contours_take_no_slots() -> Fallible<()> {
+        let raw = Mat::new_rows_cols_with_default(480, 640, core::CV_8UC3, Scalar::all(0.))?;
+        let mut mask = Mat::new_rows_cols_with_default(480, 640, core::CV_8UC1, Scalar::all(0.))?;
+        // the large square comes first by arc length, but is rejected by area
+        for rect in [Rect::new(100, 100, 200, 200), Rect::new(400, 200, 100, 40)].iter() {
+            imgproc::rectangle(
+                &mut mask,
+                *rect,
+                Scalar::all(255.),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+
+        let detector = Detector {
+            n_objects: 1,
+            min_arc_length: 50.,
+            roi: [1., 1.],
+            filter: ObjectFilter {
+                max_area: Some(10000.),
+                ..Default::default()
+            },
+            draw_position: false,
+            ..Default::default()
+        };
+        let objects = detector
+            .detect_masks(
+                &raw,
+                &[("brick".to_owned(), mask)],
+                &DetectOptions::default(),
+            )?
+            .objects;
+
+        assert_eq!(objects.len(), 1);
+        assert!((objects[0].x - 450).abs() <= 1);
+        assert!((objects[0].y - 220).abs() <= 1);
+        Ok(())
     }
 }
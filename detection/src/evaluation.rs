@@ -0,0 +1,336 @@
+use crate::{
+    dataset::{LabeledImage, LabeledObject},
+    detector::{DetectOptions, Detector, Obj},
+};
+use failure::Fallible;
+use geo::{algorithm::contains::Contains, LineString, Point, Polygon};
+use hacky_arm_common::opencv::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::{self, Display, Formatter},
+    path::PathBuf,
+};
+
+/// The criteria to match detections with ground truth objects.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvaluationOptions {
+    /// the maximum distance in pixels from a detection to the ground truth
+    /// center, unless the detection center lies in the ground truth polygon
+    pub max_center_distance: f32,
+}
+
+impl Default for EvaluationOptions {
+    fn default() -> Self {
+        Self {
+            max_center_distance: 20.0,
+        }
+    }
+}
+
+/// The matching result of an image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImageEvaluation {
+    pub file: PathBuf,
+    pub n_truths: usize,
+    pub n_detections: usize,
+    /// the center distances in pixels of matched pairs
+    pub center_errors: Vec<f32>,
+    /// the angle differences in degrees of matched pairs with ground truth angles
+    pub angle_errors: Vec<f32>,
+}
+
+/// The summary over all images.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvaluationReport {
+    pub n_images: usize,
+    pub n_truths: usize,
+    pub n_detections: usize,
+    pub n_matches: usize,
+    /// matches / detections, which is 1 without detections
+    pub precision: f64,
+    /// matches / ground truths, which is 1 without ground truths
+    pub recall: f64,
+    pub mean_center_error: Option<f32>,
+    pub max_center_error: Option<f32>,
+    pub mean_angle_error: Option<f32>,
+    pub max_angle_error: Option<f32>,
+    pub images: Vec<ImageEvaluation>,
+}
+
+/// Matches detections with ground truth objects one to one, closest pairs first.
+pub fn evaluate_image(
+    file: PathBuf,
+    truths: &[LabeledObject],
+    detections: &[Obj],
+    options: &EvaluationOptions,
+) -> ImageEvaluation {
+    // collect candidate pairs
+    let mut pairs = vec![];
+    for (truth_index, truth) in truths.iter().enumerate() {
+        let polygon = if truth.polygon.len() >= 3 {
+            let exterior: LineString<f32> = truth
+                .polygon
+                .iter()
+                .map(|&[x, y]| (x, y))
+                .collect::<Vec<_>>()
+                .into();
+            Some(Polygon::new(exterior, vec![]))
+        } else {
+            None
+        };
+
+        for (detection_index, detection) in detections.iter().enumerate() {
+            if let Some(label) = &truth.label {
+                if label != &detection.label {
+                    continue;
+                }
+            }
+            let [truth_x, truth_y] = truth.center;
+            let (x, y) = (detection.x as f32, detection.y as f32);
+            let distance = (x - truth_x).hypot(y - truth_y);
+            let inside = polygon
+                .as_ref()
+                .map(|polygon| polygon.contains(&Point::new(x, y)))
+                .unwrap_or(false);
+            if distance <= options.max_center_distance || inside {
+                pairs.push((distance, truth_index, detection_index));
+            }
+        }
+    }
+    pairs.sort_by(|lhs, rhs| lhs.0.partial_cmp(&rhs.0).unwrap());
+
+    // pick closest pairs greedily
+    let mut truth_matched = vec![false; truths.len()];
+    let mut detection_matched = vec![false; detections.len()];
+    let mut center_errors = vec![];
+    let mut angle_errors = vec![];
+    for (distance, truth_index, detection_index) in pairs {
+        if truth_matched[truth_index] || detection_matched[detection_index] {
+            continue;
+        }
+        truth_matched[truth_index] = true;
+        detection_matched[detection_index] = true;
+
+        center_errors.push(distance);
+        if let Some(angle) = truths[truth_index].angle {
+            angle_errors.push(angle_error(detections[detection_index].angle, angle));
+        }
+    }
+
+    ImageEvaluation {
+        file,
+        n_truths: truths.len(),
+        n_detections: detections.len(),
+        center_errors,
+        angle_errors,
+    }
+}
+
+/// Runs the detector over the labeled images and evaluates the detections.
+pub fn evaluate_detector(
+    detector: &Detector,
+    images: &[(LabeledImage, Mat)],
+    options: &EvaluationOptions,
+) -> Fallible<EvaluationReport> {
+    let images = images
+        .iter()
+        .map(|(image, raw)| {
+            let objects = detector.detect(raw, &DetectOptions::default())?.objects;
+            Ok(evaluate_image(
+                image.file.clone(),
+                &image.objects,
+                &objects,
+                options,
+            ))
+        })
+        .collect::<Fallible<Vec<_>>>()?;
+    Ok(EvaluationReport::new(images))
+}
+
+impl ImageEvaluation {
+    /// The number of matched pairs.
+    pub fn n_matches(&self) -> usize {
+        self.center_errors.len()
+    }
+}
+
+impl EvaluationReport {
+    /// Summarizes the image evaluations.
+    pub fn new(images: Vec<ImageEvaluation>) -> Self {
+        let n_truths = images.iter().map(|image| image.n_truths).sum();
+        let n_detections = images.iter().map(|image| image.n_detections).sum();
+        let n_matches = images.iter().map(|image| image.n_matches()).sum();
+        let ratio = |count: usize, total: usize| {
+            if total == 0 {
+                1.0
+            } else {
+                count as f64 / total as f64
+            }
+        };
+
+        let center_errors = images
+            .iter()
+            .flat_map(|image| image.center_errors.iter().cloned())
+            .collect::<Vec<_>>();
+        let angle_errors = images
+            .iter()
+            .flat_map(|image| image.angle_errors.iter().cloned())
+            .collect::<Vec<_>>();
+
+        Self {
+            n_images: images.len(),
+            n_truths,
+            n_detections,
+            n_matches,
+            precision: ratio(n_matches, n_detections),
+            recall: ratio(n_matches, n_truths),
+            mean_center_error: mean(&center_errors),
+            max_center_error: max(&center_errors),
+            mean_angle_error: mean(&angle_errors),
+            max_angle_error: max(&angle_errors),
+            images,
+        }
+    }
+
+    /// The harmonic mean of precision and recall.
+    pub fn f1_score(&self) -> f64 {
+        let sum = self.precision + self.recall;
+        if sum > 0.0 {
+            2.0 * self.precision * self.recall / sum
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Display for EvaluationReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let or_na = |value: Option<f32>| match value {
+            Some(value) => format!("{:.2}", value),
+            None => String::from("n/a"),
+        };
+
+        for image in self.images.iter() {
+            writeln!(
+                f,
+                "{}: {}/{} ground truths matched, {} detections",
+                image.file.display(),
+                image.n_matches(),
+                image.n_truths,
+                image.n_detections
+            )?;
+        }
+        writeln!(
+            f,
+            "images: {}, ground truths: {}, detections: {}, matches: {}",
+            self.n_images, self.n_truths, self.n_detections, self.n_matches
+        )?;
+        writeln!(
+            f,
+            "precision: {:.3}, recall: {:.3}, f1: {:.3}",
+            self.precision,
+            self.recall,
+            self.f1_score()
+        )?;
+        writeln!(
+            f,
+            "center error (px): mean {}, max {}",
+            or_na(self.mean_center_error),
+            or_na(self.max_center_error)
+        )?;
+        write!(
+            f,
+            "angle error (deg): mean {}, max {}",
+            or_na(self.mean_angle_error),
+            or_na(self.max_angle_error)
+        )
+    }
+}
+
+// The angle difference of rectangles in degrees, which are symmetric under
+// half turns, so the error is in [0, 90].
+fn angle_error(lhs: f32, rhs: f32) -> f32 {
+    ((lhs - rhs + 90.0).rem_euclid(180.0) - 90.0).abs()
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f32>() / values.len() as f32)
+    }
+}
+
+fn max(values: &[f32]) -> Option<f32> {
+    values.iter().cloned().fold(None, |max, value| match max {
+        Some(max) if max >= value => Some(max),
+        _ => Some(value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::measurement::Measurements;
+
+    fn detection(label: &str, x: i32, y: i32, angle: f32) -> Obj {
+        Obj {
+            label: label.to_owned(),
+            x,
+            y,
+            angle,
+            polygon: LineString(vec![]),
+            measurements: Measurements::default(),
+            shape: None,
+            shape_score: None,
+        }
+    }
+
+    #[test]
+    fn match_closest_detections() {
+        let truths = vec![
+            LabeledObject {
+                label: Some("brick".to_owned()),
+                center: [100.0, 100.0],
+                angle: Some(85.0),
+                polygon: vec![],
+            },
+            LabeledObject {
+                label: None,
+                center: [300.0, 100.0],
+                angle: None,
+                polygon: vec![[250.0, 50.0], [350.0, 50.0], [350.0, 150.0], [250.0, 150.0]],
+            },
+            LabeledObject {
+                label: Some("brick".to_owned()),
+                center: [500.0, 500.0],
+                angle: None,
+                polygon: vec![],
+            },
+        ];
+        let detections = vec![
+            detection("brick", 110, 100, 80.0),
+            detection("brick", 103, 104, -85.0),
+            detection("black", 340, 100, 0.0),
+            detection("black", 500, 500, 0.0),
+        ];
+
+        let image = evaluate_image(
+            PathBuf::from("a.jpg"),
+            &truths,
+            &detections,
+            &EvaluationOptions::default(),
+        );
+        assert_eq!(image.n_matches(), 2);
+        assert_eq!(image.center_errors, vec![5.0, 40.0]);
+        assert_eq!(image.angle_errors, vec![10.0]);
+
+        let report = EvaluationReport::new(vec![image]);
+        assert_eq!((report.n_truths, report.n_detections), (3, 4));
+        assert_eq!(report.precision, 0.5);
+        assert!((report.recall - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.mean_center_error, Some(22.5));
+        assert_eq!(report.max_angle_error, Some(10.0));
+    }
+}
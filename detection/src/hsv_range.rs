@@ -0,0 +1,166 @@
+use failure::Fallible;
+use hacky_arm_common::opencv::{
+    core::{Rect, Vec3b},
+    imgproc,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+/// The number of hue values in OpenCV, where hue ranges in [0, 180).
+const N_HUES: i32 = 180;
+
+/// The options to learn an HSV range from sampled pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HsvRangeOptions {
+    /// the percentage of outliers dropped at each end of every channel
+    pub outlier_percent: f64,
+    /// the margins added to both ends of the range in HSV order
+    pub margin: [i32; 3],
+}
+
+impl Default for HsvRangeOptions {
+    fn default() -> Self {
+        Self {
+            outlier_percent: 1.0,
+            margin: [5, 30, 30],
+        }
+    }
+}
+
+/// Collects HSV pixels within the rectangles of the BGR image.
+pub fn sample_hsv(raw: &Mat, rects: &[Rect]) -> Fallible<Vec<[u8; 3]>> {
+    let mut hsv = Mat::default()?;
+    imgproc::cvt_color(raw, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+    let rows = hsv.to_vec_2d::<Vec3b>()?;
+
+    let mut samples = vec![];
+    for rect in rects.iter() {
+        let Rect {
+            x,
+            y,
+            width,
+            height,
+        } = *rect;
+        for row in rows
+            .iter()
+            .skip(y.max(0) as usize)
+            .take(height.max(0) as usize)
+        {
+            for pixel in row
+                .iter()
+                .skip(x.max(0) as usize)
+                .take(width.max(0) as usize)
+            {
+                samples.push([pixel[0], pixel[1], pixel[2]]);
+            }
+        }
+    }
+    Ok(samples)
+}
+
+/// Computes the HSV range covering the samples except outliers, and returns
+/// the lower and upper bounds.
+///
+/// The hue range is the shortest arc on the hue circle covering the samples,
+/// so the lower hue exceeds the upper one if the range wraps around, e.g. for reds.
+pub fn learn_hsv_range(
+    samples: &[[u8; 3]],
+    options: &HsvRangeOptions,
+) -> Fallible<([i32; 3], [i32; 3])> {
+    if samples.is_empty() {
+        failure::bail!("no pixels are sampled");
+    }
+    let HsvRangeOptions {
+        outlier_percent,
+        margin: [hue_margin, saturation_margin, value_margin],
+    } = *options;
+
+    // unwrap hues so that the largest gap between sampled hues is at the end
+    let mut hues = samples.iter().map(|hsv| hsv[0] as i32).collect::<Vec<_>>();
+    hues.sort();
+    hues.dedup();
+    let (gap_end, _) = hues
+        .iter()
+        .enumerate()
+        .map(|(index, &hue)| {
+            let prev = if index == 0 {
+                hues[hues.len() - 1] - N_HUES
+            } else {
+                hues[index - 1]
+            };
+            (hue, hue - prev)
+        })
+        .max_by_key(|(_, gap)| *gap)
+        .unwrap();
+    let unwrap = |hue: u8| {
+        let hue = hue as i32;
+        if hue < gap_end {
+            hue + N_HUES
+        } else {
+            hue
+        }
+    };
+
+    let range_of = |mut values: Vec<i32>, margin: i32| {
+        values.sort();
+        let n_outliers =
+            ((values.len() as f64 * outlier_percent / 100.0) as usize).min((values.len() - 1) / 2);
+        let lower = values[n_outliers] - margin;
+        let upper = values[values.len() - 1 - n_outliers] + margin;
+        (lower, upper)
+    };
+
+    let (lower_h, upper_h) = range_of(
+        samples.iter().map(|hsv| unwrap(hsv[0])).collect(),
+        hue_margin,
+    );
+    let (lower_h, upper_h) = if upper_h - lower_h + 1 >= N_HUES {
+        (0, N_HUES - 1)
+    } else {
+        (lower_h.rem_euclid(N_HUES), upper_h.rem_euclid(N_HUES))
+    };
+
+    let (lower_s, upper_s) = range_of(
+        samples.iter().map(|hsv| hsv[1] as i32).collect(),
+        saturation_margin,
+    );
+    let (lower_v, upper_v) = range_of(
+        samples.iter().map(|hsv| hsv[2] as i32).collect(),
+        value_margin,
+    );
+
+    Ok((
+        [lower_h, lower_s.max(0), lower_v.max(0)],
+        [upper_h, upper_s.min(255), upper_v.min(255)],
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn learn_wrapped_red_range() -> Fallible<()> {
+        let options = HsvRangeOptions {
+            outlier_percent: 0.0,
+            margin: [2, 10, 10],
+        };
+
+        let samples = vec![
+            [175, 200, 100],
+            [178, 210, 120],
+            [2, 220, 110],
+            [4, 205, 90],
+        ];
+        let (lower, upper) = learn_hsv_range(&samples, &options)?;
+        assert_eq!(lower, [173, 190, 80]);
+        assert_eq!(upper, [6, 230, 130]);
+
+        let samples = vec![[100, 250, 5], [110, 240, 10]];
+        let (lower, upper) = learn_hsv_range(&samples, &options)?;
+        assert_eq!(lower, [98, 230, 0]);
+        assert_eq!(upper, [112, 255, 20]);
+        Ok(())
+    }
+}
@@ -1,3 +1,29 @@
+pub mod annotation;
+pub mod dataset;
 pub mod detector;
+pub mod evaluation;
+pub mod hsv_range;
+pub mod measurement;
+pub mod normalization;
+pub mod params;
+pub mod pipeline;
+pub mod region;
+pub mod search;
+pub mod shape;
+pub mod split;
 
-pub use detector::{Detector, Obj};
+pub use annotation::{draw_annotations, Annotation};
+pub use dataset::{Dataset, LabeledImage, LabeledObject};
+pub use detector::{ColorClass, DetectOptions, DetectionResult, Detector, Obj};
+pub use evaluation::{
+    evaluate_detector, evaluate_image, EvaluationOptions, EvaluationReport, ImageEvaluation,
+};
+pub use hsv_range::{learn_hsv_range, sample_hsv, HsvRangeOptions};
+pub use measurement::{Measurements, ObjectFilter};
+pub use normalization::Normalization;
+pub use params::DetectorParams;
+pub use pipeline::{KernelShape, Morphology, Stage};
+pub use region::Regions;
+pub use search::{search, Candidate, Dimension, Param, SearchMethod, SearchSpace};
+pub use shape::{ShapeClassifier, ShapeMatchMethod, ShapeTemplate};
+pub use split::Split;
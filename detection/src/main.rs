@@ -0,0 +1,55 @@
+use argh::FromArgs;
+use failure::Fallible;
+use hacky_detection::{evaluate_detector, Dataset, DetectorParams, EvaluationOptions};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, FromArgs)]
+/// Evaluates the detector over a labeled dataset.
+struct Args {
+    /// the dataset file listing images and ground truth objects.
+    #[argh(option, short = 'd')]
+    pub dataset: PathBuf,
+    /// the parameter file of the detector, the defaults if not given.
+    #[argh(option, short = 'p')]
+    pub params: Option<PathBuf>,
+    /// the maximum distance in pixels to match a detection with a ground truth.
+    #[argh(option)]
+    pub max_center_distance: Option<f32>,
+    /// the file to write the JSON report to.
+    #[argh(option, short = 'o')]
+    pub output: Option<PathBuf>,
+}
+
+fn main() -> Fallible<()> {
+    pretty_env_logger::init();
+    let Args {
+        dataset,
+        params,
+        max_center_distance,
+        output,
+    } = argh::from_env();
+
+    let dataset = Dataset::load(&dataset)?;
+    let detector = match params {
+        Some(path) => DetectorParams::load(&path)?.to_detector(),
+        None => DetectorParams::default().to_detector(),
+    };
+    let options = {
+        let mut options = EvaluationOptions::default();
+        if let Some(max_center_distance) = max_center_distance {
+            options.max_center_distance = max_center_distance;
+        }
+        options
+    };
+
+    let images = dataset.read_images()?;
+    let report = evaluate_detector(&detector, &images, &options)?;
+
+    println!("{}", report);
+    if let Some(output) = output {
+        std::fs::write(&output, serde_json::to_string_pretty(&report)?)?;
+        println!("saved report to {}", output.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use failure::Fallible;
+use hacky_arm_common::opencv::{
+    core::{self, Point, RotatedRect, Scalar, Size2f},
+    imgproc,
+    prelude::*,
+    types::{VectorOfPoint, VectorOfVectorOfPoint},
+};
+use serde::{Deserialize, Serialize};
+
+/// The shape and color measurements of a detected object.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Measurements {
+    /// the contour area in pixels
+    pub area: f64,
+    /// the contour perimeter in pixels
+    pub arc_length: f64,
+    /// the longer side of the minimum area rectangle
+    pub width: f32,
+    /// the shorter side of the minimum area rectangle
+    pub height: f32,
+    /// width / height, which is at least 1
+    pub aspect_ratio: f32,
+    /// contour area / rectangle area
+    pub rectangularity: f64,
+    /// contour area / convex hull area
+    pub solidity: f64,
+    /// the mean HSV within the contour
+    pub mean_hsv: [f64; 3],
+}
+
+/// The thresholds on measurements to reject blobs that are not objects.
+/// Unset thresholds are not checked.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectFilter {
+    pub min_area: Option<f64>,
+    pub max_area: Option<f64>,
+    pub min_aspect_ratio: Option<f32>,
+    pub max_aspect_ratio: Option<f32>,
+    pub min_rectangularity: Option<f64>,
+    pub min_solidity: Option<f64>,
+    pub min_mean_hsv: Option<[f64; 3]>,
+    pub max_mean_hsv: Option<[f64; 3]>,
+}
+
+impl ObjectFilter {
+    /// Checks if the measurements pass all thresholds.
+    pub fn accepts(&self, measurements: &Measurements) -> bool {
+        let Measurements {
+            area,
+            aspect_ratio,
+            rectangularity,
+            solidity,
+            mean_hsv,
+            ..
+        } = *measurements;
+
+        fn above<T: PartialOrd>(value: T, min: Option<T>) -> bool {
+            min.map(|min| value >= min).unwrap_or(true)
+        }
+        fn below<T: PartialOrd>(value: T, max: Option<T>) -> bool {
+            max.map(|max| value <= max).unwrap_or(true)
+        }
+
+        above(area, self.min_area)
+            && below(area, self.max_area)
+            && above(aspect_ratio, self.min_aspect_ratio)
+            && below(aspect_ratio, self.max_aspect_ratio)
+            && above(rectangularity, self.min_rectangularity)
+            && above(solidity, self.min_solidity)
+            && (0..3).all(|index| {
+                above(mean_hsv[index], self.min_mean_hsv.map(|hsv| hsv[index]))
+                    && below(mean_hsv[index], self.max_mean_hsv.map(|hsv| hsv[index]))
+            })
+    }
+}
+
+impl Measurements {
+    /// Measures the contour, where `hsv` is the HSV image the contour is found on.
+    pub(crate) fn new(
+        contour: &VectorOfPoint,
+        rotated_rect: &RotatedRect,
+        hsv: &Mat,
+    ) -> Fallible<Self> {
+        let area = imgproc::contour_area(contour, false)?;
+        let arc_length = imgproc::arc_length(contour, true)?;
+
+        let Size2f { width, height } = rotated_rect.size();
+        let (width, height) = if width >= height {
+            (width, height)
+        } else {
+            (height, width)
+        };
+        let aspect_ratio = if height > 0.0 { width / height } else { 0.0 };
+        let rect_area = (width * height) as f64;
+        let rectangularity = if rect_area > 0.0 {
+            area / rect_area
+        } else {
+            0.0
+        };
+
+        let mut hull = VectorOfPoint::new();
+        imgproc::convex_hull(contour, &mut hull, false, true)?;
+        let hull_area = imgproc::contour_area(&hull, false)?;
+        let solidity = if hull_area > 0.0 {
+            area / hull_area
+        } else {
+            0.0
+        };
+
+        // average over the filled contour
+        let mut mask = Mat::zeros_size(hsv.size()?, core::CV_8UC1)?.to_mat()?;
+        let mut contours = VectorOfVectorOfPoint::new();
+        contours.push(VectorOfPoint::from_iter(contour.iter()));
+        imgproc::draw_contours(
+            &mut mask,
+            &contours,
+            0,
+            Scalar::all(255.),
+            imgproc::FILLED,
+            imgproc::LINE_8,
+            &core::no_array()?,
+            i32::MAX,
+            Point::default(),
+        )?;
+        let mean = core::mean(hsv, &mask)?;
+
+        Ok(Self {
+            area,
+            arc_length,
+            width,
+            height,
+            aspect_ratio,
+            rectangularity,
+            solidity,
+            mean_hsv: [mean[0], mean[1], mean[2]],
+        })
+    }
+}
@@ -0,0 +1,183 @@
+use failure::Fallible;
+use hacky_arm_common::opencv::{
+    core::{self, Rect, Scalar, Size},
+    imgproc,
+    prelude::*,
+    types::VectorOfMat,
+};
+use serde::{Deserialize, Serialize};
+
+/// A lighting normalization step applied on the BGR input image before
+/// color thresholding, so that HSV bounds tuned in one place keep working
+/// under other lighting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Normalization {
+    /// Scales color channels so that the image averages to gray.
+    GrayWorld,
+    /// Scales color channels so that a white or gray reference in view,
+    /// e.g. a card taped on the table, becomes gray.
+    WhiteReference {
+        /// the reference area [x, y, width, height] in pixels
+        region: [i32; 4],
+    },
+    /// Equalizes the value channel by contrast limited adaptive histogram
+    /// equalization, which evens out shadows and highlights.
+    Clahe {
+        #[serde(default = "default_clip_limit")]
+        clip_limit: f64,
+        /// the number of tiles on each axis
+        #[serde(default = "default_tile_size")]
+        tile_size: i32,
+    },
+    /// Maps each channel value v in [0, 1] to v^(1/gamma), which brightens
+    /// the image if gamma exceeds 1.
+    Gamma { gamma: f64 },
+}
+
+impl Normalization {
+    /// The name of the normalization kind as written in parameter files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Normalization::GrayWorld => "gray_world",
+            Normalization::WhiteReference { .. } => "white_reference",
+            Normalization::Clahe { .. } => "clahe",
+            Normalization::Gamma { .. } => "gamma",
+        }
+    }
+
+    /// Applies the normalization on the BGR image in place.
+    pub fn apply(&self, img: &mut Mat) -> Fallible<()> {
+        if img.channels()? != 3 {
+            failure::bail!(
+                "{} normalization expects a 3-channel BGR image",
+                self.name()
+            );
+        }
+        match self {
+            Normalization::GrayWorld => {
+                let mean = core::mean(&*img, &core::no_array()?)?;
+                balance(img, mean)?;
+            }
+            Normalization::WhiteReference {
+                region: [x, y, width, height],
+            } => {
+                let Size {
+                    width: img_width,
+                    height: img_height,
+                } = img.size()?;
+                if *x < 0 || *y < 0 || x + width > img_width || y + height > img_height {
+                    failure::bail!(
+                        "the white reference region {:?} is out of the {}x{} image",
+                        [x, y, width, height],
+                        img_width,
+                        img_height
+                    );
+                }
+                let reference = Mat::roi(&*img, Rect::new(*x, *y, *width, *height))?;
+                let mean = core::mean(&reference, &core::no_array()?)?;
+                balance(img, mean)?;
+            }
+            Normalization::Clahe {
+                clip_limit,
+                tile_size,
+            } => {
+                let mut hsv = Mat::default()?;
+                imgproc::cvt_color(&*img, &mut hsv, imgproc::COLOR_BGR2HSV, 0)?;
+                let mut channels = VectorOfMat::new();
+                core::split(&hsv, &mut channels)?;
+
+                let mut clahe =
+                    imgproc::create_clahe(*clip_limit, Size::new(*tile_size, *tile_size))?;
+                let mut value = Mat::default()?;
+                clahe.apply(&channels.get(2)?, &mut value)?;
+                channels.set(2, value)?;
+
+                core::merge(&channels, &mut hsv)?;
+                imgproc::cvt_color(&hsv, img, imgproc::COLOR_HSV2BGR, 0)?;
+            }
+            Normalization::Gamma { gamma } => {
+                let mut table =
+                    Mat::new_rows_cols_with_default(1, 256, core::CV_8U, Scalar::all(0.))?;
+                for index in 0..256 {
+                    let value = (index as f64 / 255.).powf(1. / gamma) * 255.;
+                    *table.at_2d_mut::<u8>(0, index)? = value.round().min(255.) as u8;
+                }
+                core::lut(&img.clone()?, &table, img)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies the normalization steps in order, and returns the normalized image.
+pub fn normalize(raw: &Mat, normalization: &[Normalization]) -> Fallible<Mat> {
+    let mut img = raw.clone()?;
+    for step in normalization.iter() {
+        step.apply(&mut img)?;
+    }
+    Ok(img)
+}
+
+// Scales BGR channels so that the given mean color becomes gray of the same brightness.
+fn balance(img: &mut Mat, mean: Scalar) -> Fallible<()> {
+    let gray = (mean[0] + mean[1] + mean[2]) / 3.;
+    let mut channels = VectorOfMat::new();
+    core::split(&*img, &mut channels)?;
+    for index in 0..3 {
+        // leave dark channels as is rather than amplifying noise
+        if mean[index] < 1. {
+            continue;
+        }
+        let mut channel = Mat::default()?;
+        channels
+            .get(index)?
+            .convert_to(&mut channel, core::CV_8U, gray / mean[index], 0.)?;
+        channels.set(index, channel)?;
+    }
+    core::merge(&channels, img)?;
+    Ok(())
+}
+
+fn default_clip_limit() -> f64 {
+    2.0
+}
+
+fn default_tile_size() -> i32 {
+    8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gray_world_removes_tint() -> Fallible<()> {
+        let mut img = Mat::new_rows_cols_with_default(
+            480,
+            640,
+            core::CV_8UC3,
+            Scalar::new(60., 120., 180., 0.),
+        )?;
+        Normalization::GrayWorld.apply(&mut img)?;
+        let mean = core::mean(&img, &core::no_array()?)?;
+        for index in 0..3 {
+            assert!((mean[index] - 120.).abs() <= 1., "{:?}", mean);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reject_white_reference_out_of_image() -> Fallible<()> {
+        let mut img = Mat::new_rows_cols_with_default(480, 640, core::CV_8UC3, Scalar::all(128.))?;
+        let outside = Normalization::WhiteReference {
+            region: [600, 400, 100, 50],
+        };
+        assert!(outside.apply(&mut img).is_err());
+        let inside = Normalization::WhiteReference {
+            region: [500, 400, 100, 50],
+        };
+        assert!(inside.apply(&mut img).is_ok());
+        Ok(())
+    }
+}
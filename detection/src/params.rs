@@ -0,0 +1,147 @@
+use crate::{
+    detector::{ColorClass, Detector},
+    measurement::ObjectFilter,
+    normalization::Normalization,
+    pipeline::Stage,
+    region::Regions,
+    shape::ShapeClassifier,
+    split::Split,
+};
+use failure::Fallible;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// The detector fields in a parameter file, e.g. `arm/params/*.json`.
+///
+/// Unset fields keep the detector defaults, and fields unknown to the
+/// detector, e.g. the depth settings of the arm, are ignored.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DetectorParams {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inversion: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blur_kernel: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_dilations: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dilation_kernel: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_erosions: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub erosion_kernel: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_objects: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_arc_length: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_arc_length: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub roi: Option<[f64; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lower_bound: Option<[i32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upper_bound: Option<[i32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<Vec<Normalization>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Vec<Stage>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classes: Option<Vec<ColorClass>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<ObjectFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regions: Option<Regions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub split: Option<Split>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shapes: Option<ShapeClassifier>,
+}
+
+impl DetectorParams {
+    /// Loads the parameters from a JSON5 file, the same format the arm reads.
+    pub fn load<P>(path: P) -> Fallible<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let params = serde_json::from_value(Self::load_value(path)?)?;
+        Ok(params)
+    }
+
+    /// Loads a parameter file as a JSON value, which keeps the fields
+    /// unknown to the detector for tools that write the file back.
+    pub fn load_value<P>(path: P) -> Fallible<Value>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        let value = json5::from_str(&text)
+            .map_err(|err| failure::format_err!("failed to parse {}: {}", path.display(), err))?;
+        Ok(value)
+    }
+
+    /// Builds the detector, where unset fields take the default values.
+    pub fn to_detector(&self) -> Detector {
+        let mut detector = Detector::default();
+        if let Some(inversion) = self.inversion {
+            detector.inversion = inversion;
+        }
+        if let Some(blur_kernel) = self.blur_kernel {
+            detector.blur_kernel = blur_kernel;
+        }
+        if let Some(n_dilations) = self.n_dilations {
+            detector.n_dilations = n_dilations;
+        }
+        if let Some(dilation_kernel) = self.dilation_kernel {
+            detector.dilation_kernel = dilation_kernel;
+        }
+        if let Some(n_erosions) = self.n_erosions {
+            detector.n_erosions = n_erosions;
+        }
+        if let Some(erosion_kernel) = self.erosion_kernel {
+            detector.erosion_kernel = erosion_kernel;
+        }
+        if let Some(n_objects) = self.n_objects {
+            detector.n_objects = n_objects;
+        }
+        if let Some(min_arc_length) = self.min_arc_length {
+            detector.min_arc_length = min_arc_length;
+        }
+        if let Some(max_arc_length) = self.max_arc_length {
+            detector.max_arc_length = max_arc_length;
+        }
+        if let Some(roi) = self.roi {
+            detector.roi = roi;
+        }
+        if let Some(lower_bound) = self.lower_bound {
+            detector.lower_bound = lower_bound;
+        }
+        if let Some(upper_bound) = self.upper_bound {
+            detector.upper_bound = upper_bound;
+        }
+        if let Some(normalization) = &self.normalization {
+            detector.normalization = normalization.clone();
+        }
+        if let Some(pipeline) = &self.pipeline {
+            detector.pipeline = Some(pipeline.clone());
+        }
+        if let Some(classes) = &self.classes {
+            detector.classes = classes.clone();
+        }
+        if let Some(filter) = &self.filter {
+            detector.filter = filter.clone();
+        }
+        if let Some(regions) = &self.regions {
+            detector.regions = regions.clone();
+        }
+        if let Some(split) = &self.split {
+            detector.split = Some(split.clone());
+        }
+        if let Some(shapes) = &self.shapes {
+            detector.shapes = Some(shapes.clone());
+        }
+        detector
+    }
+}
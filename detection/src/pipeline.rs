@@ -0,0 +1,183 @@
+use failure::Fallible;
+use hacky_arm_common::opencv::{
+    core::{self, Point, Size},
+    imgproc,
+    prelude::*,
+    types::VectorOfi32,
+};
+use serde::{Deserialize, Serialize};
+
+/// An image processing stage that turns the input image into a binary mask.
+///
+/// Stages run in order on the BGR input image. The `hsv_threshold` stage
+/// converts the image to a mask, and the stages after it refine the mask.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Stage {
+    /// Keeps pixels within the HSV range. It uses the detector bounds if not given.
+    ///
+    /// The hue range wraps around if the lower hue exceeds the upper one.
+    HsvThreshold {
+        #[serde(default)]
+        lower_bound: Option<[i32; 3]>,
+        #[serde(default)]
+        upper_bound: Option<[i32; 3]>,
+    },
+    MedianBlur {
+        kernel: i32,
+    },
+    GaussianBlur {
+        kernel: i32,
+        /// the standard deviation, computed from kernel size if zero
+        #[serde(default)]
+        sigma: f64,
+    },
+    Invert,
+    Dilate(Morphology),
+    Erode(Morphology),
+    /// Erosion followed by dilation, which removes small blobs.
+    Open(Morphology),
+    /// Dilation followed by erosion, which fills small gaps.
+    Close(Morphology),
+}
+
+/// The parameters of a morphological operation.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Morphology {
+    #[serde(default)]
+    pub shape: KernelShape,
+    pub kernel: i32,
+    #[serde(default = "default_iterations")]
+    pub iterations: i32,
+}
+
+/// The shape of structuring element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KernelShape {
+    Rect,
+    Cross,
+    Ellipse,
+}
+
+impl Default for KernelShape {
+    fn default() -> Self {
+        KernelShape::Cross
+    }
+}
+
+impl Stage {
+    /// The name of the stage kind as written in parameter files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::HsvThreshold { .. } => "hsv_threshold",
+            Stage::MedianBlur { .. } => "median_blur",
+            Stage::GaussianBlur { .. } => "gaussian_blur",
+            Stage::Invert => "invert",
+            Stage::Dilate(_) => "dilate",
+            Stage::Erode(_) => "erode",
+            Stage::Open(_) => "open",
+            Stage::Close(_) => "close",
+        }
+    }
+
+    /// Applies the stage on the image in place.
+    pub fn apply(&self, img: &mut Mat, bounds: &([i32; 3], [i32; 3])) -> Fallible<()> {
+        match self {
+            Stage::HsvThreshold {
+                lower_bound,
+                upper_bound,
+            } => {
+                if img.channels()? != 3 {
+                    failure::bail!("hsv_threshold stage expects a 3-channel BGR image");
+                }
+                let lower_bound = lower_bound.unwrap_or(bounds.0);
+                let upper_bound = upper_bound.unwrap_or(bounds.1);
+
+                imgproc::cvt_color(&img.clone()?, img, imgproc::COLOR_BGR2HSV, 0)?;
+                let hsv = img.clone()?;
+                let in_range = |lower_bound: [i32; 3], upper_bound: [i32; 3], dst: &mut Mat| {
+                    let lower_bound = VectorOfi32::from_iter(lower_bound.iter().cloned());
+                    let upper_bound = VectorOfi32::from_iter(upper_bound.iter().cloned());
+                    core::in_range(&hsv, &lower_bound, &upper_bound, dst)
+                };
+
+                if lower_bound[0] <= upper_bound[0] {
+                    in_range(lower_bound, upper_bound, img)?;
+                } else {
+                    // take the union of [lower, 179] and [0, upper] hues
+                    let [_, lower_s, lower_v] = lower_bound;
+                    let [_, upper_s, upper_v] = upper_bound;
+                    let mut high = Mat::default()?;
+                    let mut low = Mat::default()?;
+                    in_range(lower_bound, [179, upper_s, upper_v], &mut high)?;
+                    in_range([0, lower_s, lower_v], upper_bound, &mut low)?;
+                    core::bitwise_or(&high, &low, img, &core::no_array()?)?;
+                }
+            }
+            Stage::MedianBlur { kernel } => {
+                imgproc::median_blur(&img.clone()?, img, to_odd(*kernel))?;
+            }
+            Stage::GaussianBlur { kernel, sigma } => {
+                let size = Size {
+                    width: to_odd(*kernel),
+                    height: to_odd(*kernel),
+                };
+                imgproc::gaussian_blur(
+                    &img.clone()?,
+                    img,
+                    size,
+                    *sigma,
+                    *sigma,
+                    core::BORDER_DEFAULT,
+                )?;
+            }
+            Stage::Invert => {
+                core::bitwise_not(&img.clone()?, img, &core::no_array()?)?;
+            }
+            Stage::Dilate(morphology) => morphology.apply(img, imgproc::MORPH_DILATE)?,
+            Stage::Erode(morphology) => morphology.apply(img, imgproc::MORPH_ERODE)?,
+            Stage::Open(morphology) => morphology.apply(img, imgproc::MORPH_OPEN)?,
+            Stage::Close(morphology) => morphology.apply(img, imgproc::MORPH_CLOSE)?,
+        }
+        Ok(())
+    }
+}
+
+impl Morphology {
+    fn apply(&self, img: &mut Mat, op: i32) -> Fallible<()> {
+        let shape = match self.shape {
+            KernelShape::Rect => imgproc::MORPH_RECT,
+            KernelShape::Cross => imgproc::MORPH_CROSS,
+            KernelShape::Ellipse => imgproc::MORPH_ELLIPSE,
+        };
+        let kernel: Mat = imgproc::get_structuring_element(
+            shape,
+            Size {
+                width: to_odd(self.kernel),
+                height: to_odd(self.kernel),
+            },
+            Point::new(-1, -1),
+        )?;
+        imgproc::morphology_ex(
+            &img.clone()?,
+            img,
+            op,
+            &kernel,
+            Point::new(-1, -1),
+            self.iterations,
+            core::BORDER_CONSTANT,
+            imgproc::morphology_default_border_value()?,
+        )?;
+        Ok(())
+    }
+}
+
+// OpenCV requires odd kernel sizes.
+fn to_odd(value: i32) -> i32 {
+    value.max(3) | 1
+}
+
+fn default_iterations() -> i32 {
+    1
+}
@@ -0,0 +1,61 @@
+use geo::{algorithm::contains::Contains, LineString, Point, Polygon};
+use serde::{Deserialize, Serialize};
+
+/// The polygonal regions in pixel coordinates where objects are detected.
+///
+/// An object is kept if its center lies within any include polygon, or
+/// anywhere if no include polygons are given, and lies within none of
+/// the exclude polygons.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Regions {
+    /// polygons of vertices [x, y]
+    pub include: Vec<Vec<[f32; 2]>>,
+    /// polygons of vertices [x, y], e.g. pallets, the arm base and cables
+    pub exclude: Vec<Vec<[f32; 2]>>,
+}
+
+impl Regions {
+    /// Checks if the point is within the regions.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        let point = Point::new(x, y);
+        let included = self.include.is_empty()
+            || self
+                .include
+                .iter()
+                .any(|vertices| to_polygon(vertices).contains(&point));
+        let excluded = self
+            .exclude
+            .iter()
+            .any(|vertices| to_polygon(vertices).contains(&point));
+        included && !excluded
+    }
+}
+
+fn to_polygon(vertices: &[[f32; 2]]) -> Polygon<f32> {
+    let exterior: LineString<f32> = vertices
+        .iter()
+        .map(|&[x, y]| (x, y))
+        .collect::<Vec<_>>()
+        .into();
+    Polygon::new(exterior, vec![])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_and_exclude_polygons() {
+        let regions = Regions {
+            include: vec![vec![[0.0, 0.0], [100.0, 0.0], [100.0, 100.0], [0.0, 100.0]]],
+            exclude: vec![vec![[40.0, 40.0], [60.0, 40.0], [50.0, 60.0]]],
+        };
+        assert!(regions.contains(10.0, 90.0));
+        assert!(!regions.contains(50.0, 45.0));
+        assert!(!regions.contains(50.0, 150.0));
+        assert!(!regions.contains(150.0, 50.0));
+
+        assert!(Regions::default().contains(-10.0, 1e4));
+    }
+}
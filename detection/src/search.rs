@@ -0,0 +1,473 @@
+use crate::{evaluation::EvaluationReport, params::DetectorParams};
+use failure::Fallible;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::HashMap};
+
+/// A detector parameter to search over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Param {
+    BlurKernel,
+    DilationKernel,
+    NDilations,
+    ErosionKernel,
+    NErosions,
+    MinArcLength,
+    MaxArcLength,
+    LowerHue,
+    LowerSaturation,
+    LowerValue,
+    UpperHue,
+    UpperSaturation,
+    UpperValue,
+}
+
+/// The values a parameter takes, from min to max by step.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Dimension {
+    pub param: Param,
+    pub min: f64,
+    pub max: f64,
+    pub step: f64,
+}
+
+/// The search strategy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SearchMethod {
+    /// Tries every combination of values.
+    Grid,
+    /// Tries combinations of values drawn uniformly at random.
+    Random {
+        n_samples: usize,
+        #[serde(default)]
+        seed: u64,
+    },
+    /// Starts from the base parameters and tries all values of one parameter
+    /// at a time, keeping the best, until a round brings no improvement.
+    CoordinateDescent {
+        #[serde(default = "default_max_rounds")]
+        max_rounds: usize,
+    },
+}
+
+/// The search configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchSpace {
+    pub method: SearchMethod,
+    pub dimensions: Vec<Dimension>,
+}
+
+/// An evaluated set of parameters.
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    /// the searched parameters and their values
+    pub values: Vec<(Param, f64)>,
+    pub params: DetectorParams,
+    pub report: EvaluationReport,
+}
+
+impl Param {
+    /// Sets the parameter, where HSV components keep the other components of
+    /// the bounds.
+    pub fn apply(self, params: &mut DetectorParams, value: f64) {
+        let integer = value.round() as i32;
+        let defaults = params.to_detector();
+        let set_bound = |bound: &mut Option<[i32; 3]>, default: [i32; 3], index: usize| {
+            let mut new_bound = bound.unwrap_or(default);
+            new_bound[index] = integer;
+            *bound = Some(new_bound);
+        };
+
+        match self {
+            Param::BlurKernel => params.blur_kernel = Some(integer),
+            Param::DilationKernel => params.dilation_kernel = Some(integer),
+            Param::NDilations => params.n_dilations = Some(integer),
+            Param::ErosionKernel => params.erosion_kernel = Some(integer),
+            Param::NErosions => params.n_erosions = Some(integer),
+            Param::MinArcLength => params.min_arc_length = Some(value),
+            Param::MaxArcLength => params.max_arc_length = Some(value),
+            Param::LowerHue => set_bound(&mut params.lower_bound, defaults.lower_bound, 0),
+            Param::LowerSaturation => set_bound(&mut params.lower_bound, defaults.lower_bound, 1),
+            Param::LowerValue => set_bound(&mut params.lower_bound, defaults.lower_bound, 2),
+            Param::UpperHue => set_bound(&mut params.upper_bound, defaults.upper_bound, 0),
+            Param::UpperSaturation => set_bound(&mut params.upper_bound, defaults.upper_bound, 1),
+            Param::UpperValue => set_bound(&mut params.upper_bound, defaults.upper_bound, 2),
+        }
+    }
+
+    /// The value of the parameter, or the detector default if not set.
+    pub fn get(self, params: &DetectorParams) -> f64 {
+        let detector = params.to_detector();
+        match self {
+            Param::BlurKernel => detector.blur_kernel as f64,
+            Param::DilationKernel => detector.dilation_kernel as f64,
+            Param::NDilations => detector.n_dilations as f64,
+            Param::ErosionKernel => detector.erosion_kernel as f64,
+            Param::NErosions => detector.n_erosions as f64,
+            Param::MinArcLength => detector.min_arc_length,
+            Param::MaxArcLength => detector.max_arc_length,
+            Param::LowerHue => detector.lower_bound[0] as f64,
+            Param::LowerSaturation => detector.lower_bound[1] as f64,
+            Param::LowerValue => detector.lower_bound[2] as f64,
+            Param::UpperHue => detector.upper_bound[0] as f64,
+            Param::UpperSaturation => detector.upper_bound[1] as f64,
+            Param::UpperValue => detector.upper_bound[2] as f64,
+        }
+    }
+
+    fn is_hsv(self) -> bool {
+        matches!(
+            self,
+            Param::LowerHue
+                | Param::LowerSaturation
+                | Param::LowerValue
+                | Param::UpperHue
+                | Param::UpperSaturation
+                | Param::UpperValue
+        )
+    }
+
+    fn is_kernel(self) -> bool {
+        matches!(
+            self,
+            Param::BlurKernel | Param::DilationKernel | Param::ErosionKernel
+        )
+    }
+
+    // the parameters only used by the default pipeline
+    fn is_default_stage(self) -> bool {
+        self.is_kernel() || matches!(self, Param::NDilations | Param::NErosions)
+    }
+}
+
+impl Dimension {
+    /// Lists the values from min to max by step.
+    pub fn values(&self) -> Fallible<Vec<f64>> {
+        let Dimension {
+            param,
+            min,
+            max,
+            step,
+        } = *self;
+        let valid = step > 0.0 && min <= max;
+        if !valid {
+            failure::bail!(
+                "{:?} expects min <= max and a positive step, but get min {}, max {}, step {}",
+                param,
+                min,
+                max,
+                step
+            );
+        }
+        // OpenCV requires odd kernel sizes
+        let odd = |value: f64| value.fract() == 0.0 && value.rem_euclid(2.0) == 1.0;
+        let even = |value: f64| value.fract() == 0.0 && value.rem_euclid(2.0) == 0.0;
+        if param.is_kernel() && !(odd(min) && even(step)) {
+            failure::bail!(
+                "{:?} expects an odd min and an even step, but get min {}, step {}",
+                param,
+                min,
+                step
+            );
+        }
+        let n_steps = ((max - min) / step + 1e-9).floor() as usize;
+        Ok((0..=n_steps)
+            .map(|index| min + step * index as f64)
+            .collect())
+    }
+}
+
+/// Orders reports from the best, by F1 score and then by mean center error.
+pub fn compare_reports(lhs: &EvaluationReport, rhs: &EvaluationReport) -> Ordering {
+    let center_error =
+        |report: &EvaluationReport| report.mean_center_error.unwrap_or(f32::INFINITY);
+    rhs.f1_score()
+        .partial_cmp(&lhs.f1_score())
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| {
+            center_error(lhs)
+                .partial_cmp(&center_error(rhs))
+                .unwrap_or(Ordering::Equal)
+        })
+}
+
+/// Searches the parameters on top of the base parameters, and returns the
+/// evaluated candidates from the best.
+///
+/// The evaluation function runs the detector built from the parameters
+/// over a dataset.
+pub fn search<F>(
+    base: &DetectorParams,
+    space: &SearchSpace,
+    mut evaluate: F,
+) -> Fallible<Vec<Candidate>>
+where
+    F: FnMut(&DetectorParams) -> Fallible<EvaluationReport>,
+{
+    let SearchSpace { method, dimensions } = space;
+    if dimensions.is_empty() {
+        failure::bail!("no parameters to search");
+    }
+    if base.classes.is_some() && dimensions.iter().any(|dim| dim.param.is_hsv()) {
+        failure::bail!("HSV bounds cannot be searched since color classes replace them");
+    }
+    let custom_pipeline = base.pipeline.is_some()
+        || base
+            .classes
+            .iter()
+            .flatten()
+            .any(|class| class.pipeline.is_some());
+    if custom_pipeline && dimensions.iter().any(|dim| dim.param.is_default_stage()) {
+        failure::bail!(
+            "blur and morphology parameters cannot be searched since a custom pipeline replaces them"
+        );
+    }
+    let grid = dimensions
+        .iter()
+        .map(|dim| dim.values())
+        .collect::<Fallible<Vec<_>>>()?;
+
+    // evaluates the indices of values in each dimension once
+    let mut candidates: Vec<Candidate> = vec![];
+    let mut visited: HashMap<Vec<usize>, usize> = HashMap::new();
+    let mut visit = |indices: &[usize]| -> Fallible<EvaluationReport> {
+        if let Some(&index) = visited.get(indices) {
+            return Ok(candidates[index].report.clone());
+        }
+        let mut params = base.clone();
+        let values = dimensions
+            .iter()
+            .zip(indices.iter())
+            .zip(grid.iter())
+            .map(|((dim, &index), values)| {
+                dim.param.apply(&mut params, values[index]);
+                (dim.param, values[index])
+            })
+            .collect();
+        let report = evaluate(&params)?;
+        visited.insert(indices.to_vec(), candidates.len());
+        candidates.push(Candidate {
+            values,
+            params,
+            report: report.clone(),
+        });
+        Ok(report)
+    };
+
+    match *method {
+        SearchMethod::Grid => {
+            let mut indices = vec![0; grid.len()];
+            'grid: loop {
+                visit(&indices)?;
+                // advance like an odometer
+                for (index, values) in indices.iter_mut().zip(grid.iter()) {
+                    *index += 1;
+                    if *index < values.len() {
+                        continue 'grid;
+                    }
+                    *index = 0;
+                }
+                break;
+            }
+        }
+        SearchMethod::Random { n_samples, seed } => {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for _ in 0..n_samples {
+                let indices = grid
+                    .iter()
+                    .map(|values| rng.gen_range(0, values.len()))
+                    .collect::<Vec<_>>();
+                visit(&indices)?;
+            }
+        }
+        SearchMethod::CoordinateDescent { max_rounds } => {
+            // start from the values closest to the base parameters
+            let mut best_indices = dimensions
+                .iter()
+                .zip(grid.iter())
+                .map(|(dim, values)| {
+                    let current = dim.param.get(base);
+                    (0..values.len())
+                        .min_by(|&lhs, &rhs| {
+                            let lhs = (values[lhs] - current).abs();
+                            let rhs = (values[rhs] - current).abs();
+                            lhs.partial_cmp(&rhs).unwrap()
+                        })
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            let mut best_report = visit(&best_indices)?;
+
+            for _ in 0..max_rounds {
+                let mut improved = false;
+                for (dim_index, values) in grid.iter().enumerate() {
+                    for value_index in 0..values.len() {
+                        let mut indices = best_indices.clone();
+                        indices[dim_index] = value_index;
+                        let report = visit(&indices)?;
+                        if compare_reports(&report, &best_report) == Ordering::Less {
+                            best_indices = indices;
+                            best_report = report;
+                            improved = true;
+                        }
+                    }
+                }
+                if !improved {
+                    break;
+                }
+            }
+        }
+    }
+
+    candidates.sort_by(|lhs, rhs| compare_reports(&lhs.report, &rhs.report));
+    Ok(candidates)
+}
+
+fn default_max_rounds() -> usize {
+    5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{detector::ColorClass, evaluation::ImageEvaluation, pipeline::Stage};
+    use std::path::PathBuf;
+
+    // pretends the dataset is detected perfectly by blur kernel 9 and
+    // min arc length 100, and degrades away from them
+    fn evaluate(params: &DetectorParams) -> Fallible<EvaluationReport> {
+        let blur_kernel = params.blur_kernel.unwrap();
+        let min_arc_length = params.min_arc_length.unwrap();
+        let n_misses = ((blur_kernel - 9).abs() / 2) as usize
+            + ((min_arc_length - 100.0).abs() / 50.0) as usize;
+        let image = ImageEvaluation {
+            file: PathBuf::from("a.jpg"),
+            n_truths: 10,
+            n_detections: 10,
+            center_errors: vec![1.0; 10usize.saturating_sub(n_misses)],
+            angle_errors: vec![],
+        };
+        Ok(EvaluationReport::new(vec![image]))
+    }
+
+    #[test]
+    fn search_methods_find_best() -> Fallible<()> {
+        let base = DetectorParams {
+            blur_kernel: Some(41),
+            min_arc_length: Some(250.0),
+            ..Default::default()
+        };
+        let dimensions = vec![
+            Dimension {
+                param: Param::BlurKernel,
+                min: 3.0,
+                max: 41.0,
+                step: 2.0,
+            },
+            Dimension {
+                param: Param::MinArcLength,
+                min: 0.0,
+                max: 300.0,
+                step: 50.0,
+            },
+        ];
+
+        let grid = search(
+            &base,
+            &SearchSpace {
+                method: SearchMethod::Grid,
+                dimensions: dimensions.clone(),
+            },
+            evaluate,
+        )?;
+        assert_eq!(grid.len(), 20 * 7);
+        assert_eq!(
+            grid[0].values,
+            vec![(Param::BlurKernel, 9.0), (Param::MinArcLength, 100.0)]
+        );
+        assert_eq!(grid[0].params.blur_kernel, Some(9));
+        assert_eq!(grid[0].report.f1_score(), 1.0);
+
+        let descent = search(
+            &base,
+            &SearchSpace {
+                method: SearchMethod::CoordinateDescent { max_rounds: 5 },
+                dimensions: dimensions.clone(),
+            },
+            evaluate,
+        )?;
+        assert_eq!(descent[0].values, grid[0].values);
+        assert!(descent.len() < grid.len());
+
+        let random = search(
+            &base,
+            &SearchSpace {
+                method: SearchMethod::Random {
+                    n_samples: 10,
+                    seed: 7,
+                },
+                dimensions,
+            },
+            evaluate,
+        )?;
+        assert!(!random.is_empty() && random.len() <= 10);
+        Ok(())
+    }
+
+    #[test]
+    fn reject_params_replaced_by_pipeline() {
+        let space = SearchSpace {
+            method: SearchMethod::Grid,
+            dimensions: vec![Dimension {
+                param: Param::NDilations,
+                min: 0.0,
+                max: 3.0,
+                step: 1.0,
+            }],
+        };
+        let stages = vec![Stage::MedianBlur { kernel: 5 }];
+
+        let base = DetectorParams {
+            pipeline: Some(stages.clone()),
+            ..Default::default()
+        };
+        assert!(search(&base, &space, evaluate).is_err());
+
+        let base = DetectorParams {
+            classes: Some(vec![ColorClass {
+                label: "brick".to_owned(),
+                lower_bound: [0, 57, 0],
+                upper_bound: [26, 158, 255],
+                inversion: None,
+                pipeline: Some(stages),
+            }]),
+            ..Default::default()
+        };
+        assert!(search(&base, &space, evaluate).is_err());
+    }
+
+    #[test]
+    fn reject_even_kernels() {
+        let dimension = |min, step| Dimension {
+            param: Param::BlurKernel,
+            min,
+            max: 41.0,
+            step,
+        };
+        assert!(dimension(3.0, 2.0).values().is_ok());
+        assert!(dimension(4.0, 2.0).values().is_err());
+        assert!(dimension(3.0, 1.0).values().is_err());
+    }
+
+    #[test]
+    fn set_hsv_components() {
+        let mut params = DetectorParams::default();
+        Param::UpperHue.apply(&mut params, 170.0);
+        Param::LowerValue.apply(&mut params, 20.4);
+        assert_eq!(params.upper_bound, Some([170, 158, 255]));
+        assert_eq!(params.lower_bound, Some([0, 57, 20]));
+        assert_eq!(Param::UpperHue.get(&params), 170.0);
+    }
+}
@@ -0,0 +1,166 @@
+use failure::Fallible;
+use hacky_arm_common::opencv::{
+    core::Point,
+    imgproc,
+    prelude::*,
+    types::{VectorOfPoint, VectorOfVectorOfPoint},
+};
+use serde::{Deserialize, Serialize};
+
+/// A reference contour of a known shape, e.g. a pen cap or a brick.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShapeTemplate {
+    pub label: String,
+    /// the contour of points [x, y] in pixels
+    pub contour: Vec<[i32; 2]>,
+}
+
+/// The Hu moment distance used by `match_shapes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShapeMatchMethod {
+    I1,
+    I2,
+    I3,
+}
+
+impl Default for ShapeMatchMethod {
+    fn default() -> Self {
+        ShapeMatchMethod::I1
+    }
+}
+
+/// Classifies object contours by the closest template in Hu moments, which
+/// is invariant to translation, rotation and scale.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShapeClassifier {
+    pub templates: Vec<ShapeTemplate>,
+    pub method: ShapeMatchMethod,
+    /// the maximum distance to the closest template, beyond which the shape
+    /// is unknown, unchecked if not set
+    pub max_distance: Option<f64>,
+    /// drops objects of unknown shapes
+    pub reject_unknown: bool,
+}
+
+impl ShapeTemplate {
+    /// Takes the largest external contour in the binary mask as the template.
+    pub fn from_mask(label: &str, mask: &Mat) -> Fallible<Self> {
+        let mut contours = VectorOfVectorOfPoint::new();
+        imgproc::find_contours(
+            mask,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+            Point::default(),
+        )?;
+
+        let mut largest = None;
+        let mut largest_area = 0.;
+        for contour in contours.iter() {
+            let area = imgproc::contour_area(&contour, false)?;
+            if area > largest_area {
+                largest_area = area;
+                largest = Some(contour);
+            }
+        }
+        let contour = match largest {
+            Some(contour) => contour,
+            None => failure::bail!("no contours are found for shape {:?}", label),
+        };
+
+        Ok(Self {
+            label: label.to_owned(),
+            contour: contour.iter().map(|Point { x, y }| [x, y]).collect(),
+        })
+    }
+}
+
+impl ShapeClassifier {
+    /// Finds the closest template, and returns its label and the distance,
+    /// where the label is `None` if the distance exceeds the maximum.
+    pub fn classify(&self, contour: &VectorOfPoint) -> Fallible<(Option<String>, Option<f64>)> {
+        let method = match self.method {
+            ShapeMatchMethod::I1 => imgproc::CONTOURS_MATCH_I1,
+            ShapeMatchMethod::I2 => imgproc::CONTOURS_MATCH_I2,
+            ShapeMatchMethod::I3 => imgproc::CONTOURS_MATCH_I3,
+        };
+
+        let mut closest: Option<(&str, f64)> = None;
+        for template in self.templates.iter() {
+            let template_contour =
+                VectorOfPoint::from_iter(template.contour.iter().map(|&[x, y]| Point::new(x, y)));
+            let distance = imgproc::match_shapes(contour, &template_contour, method, 0.)?;
+            match closest {
+                Some((_, closest_distance)) if closest_distance <= distance => {}
+                _ => closest = Some((&template.label, distance)),
+            }
+        }
+
+        let (label, distance) = match closest {
+            Some(closest) => closest,
+            None => return Ok((None, None)),
+        };
+        let known = self
+            .max_distance
+            .map(|max_distance| distance <= max_distance)
+            .unwrap_or(true);
+        let label = if known { Some(label.to_owned()) } else { None };
+        Ok((label, Some(distance)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the polygon rotated by the angle in degrees and scaled about the origin, then moved
+    fn transform(points: &[[f64; 2]], angle: f64, scale: f64, offset: [f64; 2]) -> VectorOfPoint {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        VectorOfPoint::from_iter(points.iter().map(|&[x, y]| {
+            Point::new(
+                (scale * (x * cos - y * sin) + offset[0]).round() as i32,
+                (scale * (x * sin + y * cos) + offset[1]).round() as i32,
+            )
+        }))
+    }
+
+    fn template(label: &str, points: &[[f64; 2]]) -> ShapeTemplate {
+        ShapeTemplate {
+            label: label.to_owned(),
+            contour: transform(points, 0., 1., [100., 100.])
+                .iter()
+                .map(|Point { x, y }| [x, y])
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn classify_rotated_and_scaled_shapes() -> Fallible<()> {
+        let brick = [[-50., -20.], [50., -20.], [50., 20.], [-50., 20.]];
+        let triangle = [[0., -40.], [35., 20.], [-35., 20.]];
+        let classifier = ShapeClassifier {
+            templates: vec![template("brick", &brick), template("triangle", &triangle)],
+            ..Default::default()
+        };
+
+        let contour = transform(&brick, 30., 2., [300., 300.]);
+        let (label, _) = classifier.classify(&contour)?;
+        assert_eq!(label, Some(String::from("brick")));
+        let contour = transform(&triangle, -75., 1.5, [300., 300.]);
+        let (label, _) = classifier.classify(&contour)?;
+        assert_eq!(label, Some(String::from("triangle")));
+
+        // only the brick is known, so the triangle is too far off
+        let classifier = ShapeClassifier {
+            templates: vec![template("brick", &brick)],
+            max_distance: Some(0.1),
+            ..Default::default()
+        };
+        let (label, distance) = classifier.classify(&contour)?;
+        assert_eq!(label, None);
+        assert!(distance.unwrap() > 0.1);
+        Ok(())
+    }
+}
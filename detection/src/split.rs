@@ -0,0 +1,192 @@
+use failure::Fallible;
+use hacky_arm_common::opencv::{
+    core::{self, Point, Size},
+    imgproc,
+    prelude::*,
+    types::{VectorOfPoint, VectorOfVectorOfPoint, VectorOfi32},
+};
+use serde::{Deserialize, Serialize};
+
+/// The parameters to separate touching objects in a mask by distance
+/// transform and watershed.
+///
+/// Pixels far enough from the mask border seed one object per connected
+/// component, and the watershed grows the seeds back to object borders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Split {
+    /// pixels whose distance to the border exceeds the ratio of the maximum
+    /// distance in the mask become seeds, in (0, 1)
+    #[serde(default = "default_distance_ratio")]
+    pub distance_ratio: f64,
+    /// the number of dilations to find the surely background area
+    #[serde(default = "default_background_iterations")]
+    pub background_iterations: i32,
+}
+
+impl Default for Split {
+    fn default() -> Self {
+        Self {
+            distance_ratio: default_distance_ratio(),
+            background_iterations: default_background_iterations(),
+        }
+    }
+}
+
+impl Split {
+    /// Finds the external contours of separated objects in the binary mask,
+    /// where `raw` is the BGR image the mask comes from.
+    pub fn contours(&self, raw: &Mat, mask: &Mat) -> Fallible<Vec<VectorOfPoint>> {
+        // find seeds far from the border
+        let mut distance = Mat::default()?;
+        imgproc::distance_transform(
+            mask,
+            &mut distance,
+            imgproc::DIST_L2,
+            imgproc::DIST_MASK_5,
+            core::CV_32F,
+        )?;
+        let mut max_distance = 0.;
+        core::min_max_loc(
+            &distance,
+            &mut 0.,
+            &mut max_distance,
+            &mut Point::default(),
+            &mut Point::default(),
+            &core::no_array()?,
+        )?;
+        if max_distance <= 0. {
+            return Ok(vec![]);
+        }
+        let mut foreground = Mat::default()?;
+        imgproc::threshold(
+            &distance,
+            &mut foreground,
+            max_distance * self.distance_ratio,
+            255.,
+            imgproc::THRESH_BINARY,
+        )?;
+        foreground
+            .clone()?
+            .convert_to(&mut foreground, core::CV_8U, 1., 0.)?;
+
+        // the area between the seeds and the surely background is unknown
+        let mut background = Mat::default()?;
+        let kernel = imgproc::get_structuring_element(
+            imgproc::MORPH_RECT,
+            Size::new(3, 3),
+            Point::new(-1, -1),
+        )?;
+        imgproc::dilate(
+            mask,
+            &mut background,
+            &kernel,
+            Point::new(-1, -1),
+            self.background_iterations,
+            core::BORDER_CONSTANT,
+            imgproc::morphology_default_border_value()?,
+        )?;
+        let mut unknown = Mat::default()?;
+        core::subtract(
+            &background,
+            &foreground,
+            &mut unknown,
+            &core::no_array()?,
+            -1,
+        )?;
+        let mut known = Mat::default()?;
+        core::bitwise_not(&unknown, &mut known, &core::no_array()?)?;
+
+        // label seeds from 2, the background as 1 and the unknown area as 0
+        let mut labels = Mat::default()?;
+        let n_labels = imgproc::connected_components(&foreground, &mut labels, 8, core::CV_32S)?;
+        let ones = Mat::ones_size(labels.size()?, core::CV_32S)?.to_mat()?;
+        let mut markers = Mat::zeros_size(labels.size()?, core::CV_32S)?.to_mat()?;
+        core::add(&labels, &ones, &mut markers, &known, -1)?;
+
+        imgproc::watershed(raw, &mut markers)?;
+
+        // collect contours of each object region
+        let mut contours = vec![];
+        for label in 2..=n_labels {
+            let bound = VectorOfi32::from_iter(vec![label]);
+            let mut region = Mat::default()?;
+            core::in_range(&markers, &bound, &bound, &mut region)?;
+            // the watershed may grow beyond the mask into the unknown area
+            core::bitwise_and(&region.clone()?, mask, &mut region, &core::no_array()?)?;
+
+            let mut region_contours = VectorOfVectorOfPoint::new();
+            imgproc::find_contours(
+                &region,
+                &mut region_contours,
+                imgproc::RETR_EXTERNAL,
+                imgproc::CHAIN_APPROX_SIMPLE,
+                Point::default(),
+            )?;
+            contours.extend(region_contours.to_vec());
+        }
+
+        Ok(contours)
+    }
+}
+
+fn default_distance_ratio() -> f64 {
+    0.5
+}
+
+fn default_background_iterations() -> i32 {
+    3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hacky_arm_common::opencv::core::{Rect, Scalar};
+
+    #[test]
+    fn split_touching_rectangles() -> Fallible<()> {
+        let mut raw = Mat::new_rows_cols_with_default(400, 400, core::CV_8UC3, Scalar::all(0.))?;
+        let mut mask = Mat::new_rows_cols_with_default(400, 400, core::CV_8UC1, Scalar::all(0.))?;
+        // the squares share a short edge, so the mask is a single blob
+        let rects = [Rect::new(100, 100, 100, 100), Rect::new(200, 170, 100, 100)];
+        for rect in rects.iter() {
+            imgproc::rectangle(
+                &mut raw,
+                *rect,
+                Scalar::all(255.),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )?;
+            imgproc::rectangle(
+                &mut mask,
+                *rect,
+                Scalar::all(255.),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                0,
+            )?;
+        }
+
+        let mut centers = Split::default()
+            .contours(&raw, &mask)?
+            .iter()
+            .map(|contour| {
+                // the watershed seam is not exactly on the shared edge, so
+                // compare centroids rather than bounding boxes
+                let moments = imgproc::moments(contour, false)?;
+                Ok((
+                    (moments.m10 / moments.m00).round() as i32,
+                    (moments.m01 / moments.m00).round() as i32,
+                ))
+            })
+            .collect::<Fallible<Vec<_>>>()?;
+        centers.sort();
+
+        assert_eq!(centers.len(), 2);
+        for ((x, y), (expect_x, expect_y)) in centers.iter().zip([(150, 150), (250, 220)].iter()) {
+            assert!((x - expect_x).abs() <= 3, "x {} != {}", x, expect_x);
+            assert!((y - expect_y).abs() <= 3, "y {} != {}", y, expect_y);
+        }
+        Ok(())
+    }
+}